@@ -40,7 +40,7 @@ fn preferences_bench(c: &mut Criterion) {
                 let bundles = loc.bundles();
 
                 for key in l10n_keys.iter() {
-                    bundles.format_value_sync(&key.0, key.1.as_ref(), &mut errors);
+                    let _ = bundles.format_value_sync(&key.0, key.1.as_ref(), &mut errors);
                 }
             })
         });
@@ -58,7 +58,7 @@ fn preferences_bench(c: &mut Criterion) {
                 let mut errors = vec![];
                 let loc = Localization::with_env(res_ids.clone(), true, env.clone(), reg.clone());
                 let bundles = loc.bundles();
-                bundles.format_messages_sync(&keys, &mut errors);
+                let _ = bundles.format_messages_sync(&keys, &mut errors);
             })
         });
     }
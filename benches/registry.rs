@@ -48,20 +48,20 @@ fn registry_bench(c: &mut Criterion) {
     let fetcher = TestFileFetcher::new();
     let (_, reg) = fetcher.get_registry_and_environment(setup);
 
-    group.bench_function(&format!("serial",), |b| {
+    group.bench_function("serial", |b| {
         b.iter(|| {
             let lang_ids = vec![en_us.clone()];
-            let mut i = reg.generate_bundles_sync(lang_ids.into_iter(), get_paths());
-            while let Some(_) = i.next() {}
+            let i = reg.generate_bundles_sync(lang_ids.into_iter(), get_paths());
+            for _ in i {}
         })
     });
 
     let rt = tokio::runtime::Runtime::new().unwrap();
-    group.bench_function(&format!("parallel",), |b| {
+    group.bench_function("parallel", |b| {
         b.iter(|| {
             let lang_ids = vec![en_us.clone()];
             let mut i = reg.generate_bundles(lang_ids.into_iter(), get_paths());
-            rt.block_on(async { while let Some(_) = i.next().await {} });
+            rt.block_on(async { while i.next().await.is_some() {} });
         })
     });
 
@@ -105,20 +105,20 @@ fn registry_metasource_bench(c: &mut Criterion) {
     let fetcher = TestFileFetcher::new();
     let (_, reg) = fetcher.get_registry_and_environment(setup);
 
-    group.bench_function(&format!("serial",), |b| {
+    group.bench_function("serial", |b| {
         b.iter(|| {
             let lang_ids = vec![en_us.clone()];
-            let mut i = reg.generate_bundles_sync(lang_ids.into_iter(), get_paths());
-            while let Some(_) = i.next() {}
+            let i = reg.generate_bundles_sync(lang_ids.into_iter(), get_paths());
+            for _ in i {}
         })
     });
 
     let rt = tokio::runtime::Runtime::new().unwrap();
-    group.bench_function(&format!("parallel",), |b| {
+    group.bench_function("parallel", |b| {
         b.iter(|| {
             let lang_ids = vec![en_us.clone()];
             let mut i = reg.generate_bundles(lang_ids.into_iter(), get_paths());
-            rt.block_on(async { while let Some(_) = i.next().await {} });
+            rt.block_on(async { while i.next().await.is_some() {} });
         })
     });
 
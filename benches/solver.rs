@@ -94,7 +94,7 @@ fn solver_bench(c: &mut Criterion) {
             values: scenario.values.clone(),
         };
 
-        group.bench_function(&format!("serial/{}", &scenario.name), |b| {
+        group.bench_function(format!("serial/{}", &scenario.name), |b| {
             b.iter(|| {
                 let mut gen = SerialProblemSolver::new(scenario.width, scenario.depth);
                 while let Ok(Some(_)) = gen.try_next(&tester, false) {}
@@ -104,11 +104,11 @@ fn solver_bench(c: &mut Criterion) {
         {
             let rt = tokio::runtime::Runtime::new().unwrap();
 
-            group.bench_function(&format!("parallel/{}", &scenario.name), |b| {
+            group.bench_function(format!("parallel/{}", &scenario.name), |b| {
                 b.iter(|| {
                     let gen = ParallelProblemSolver::new(scenario.width, scenario.depth);
                     let mut t = TestStream::new(gen, &tester);
-                    rt.block_on(async { while let Some(_) = t.next().await {} });
+                    rt.block_on(async { while t.next().await.is_some() {} });
                 })
             });
         }
@@ -3,6 +3,7 @@ use criterion::criterion_main;
 use criterion::Criterion;
 
 use fluent_testing::get_scenarios;
+use l10nregistry::source::{FileFetcher, FileSource, SourceStats};
 use l10nregistry::testing::TestFileFetcher;
 
 use unic_langid::LanguageIdentifier;
@@ -36,7 +37,66 @@ fn source_bench(c: &mut Criterion) {
             b.iter(|| {
                 for source in &sources {
                     for res_id in &res_ids {
-                        source.has_file(&locales[0], &res_id);
+                        source.has_file(&locales[0], res_id);
+                    }
+                }
+            })
+        });
+
+        let scenario_locale = locales[0].to_string();
+        let indexes: Vec<(Vec<String>, String)> = scenario
+            .file_sources
+            .iter()
+            .map(|s| {
+                let locales = get_locales(&s.locales);
+                let index = locales
+                    .iter()
+                    .flat_map(|locale| {
+                        let pre_path = s.path_scheme.replace("{locale}", &locale.to_string());
+                        res_ids
+                            .iter()
+                            .map(move |res_id| format!("{}{}", pre_path, res_id.value))
+                    })
+                    .filter(|path| fetcher.fetch_sync(&path.as_str().into()).is_ok())
+                    .collect::<Vec<_>>();
+                let pre_path = s.path_scheme.replace("{locale}", &scenario_locale);
+                (index, pre_path)
+            })
+            .collect();
+
+        // The lookup of indexed sources before the index was hashed: a scan
+        // of all paths for the full path of every probe.
+        group.bench_function(format!("{}/has_file_vec_scan", scenario.name), |b| {
+            b.iter(|| {
+                for (index, pre_path) in &indexes {
+                    for res_id in &res_ids {
+                        let full_path = format!("{}{}", pre_path, res_id.value);
+                        index.iter().any(|p| p == &full_path);
+                    }
+                }
+            })
+        });
+
+        let indexed_sources: Vec<_> = scenario
+            .file_sources
+            .iter()
+            .zip(&indexes)
+            .map(|(s, (index, _))| {
+                fetcher.get_test_file_source_with_index(
+                    &s.name,
+                    None,
+                    get_locales(&s.locales),
+                    &s.path_scheme,
+                    index.iter().map(|s| s.as_str()).collect(),
+                )
+            })
+            .collect();
+
+        group.bench_function(format!("{}/has_file_indexed", scenario.name), |b| {
+            b.iter(|| {
+                for source in &indexed_sources {
+                    for res_id in &res_ids {
+                        source.has_file(&locales[0], res_id);
                     }
                 }
             })
@@ -46,11 +106,14 @@ fn source_bench(c: &mut Criterion) {
             b.iter(|| {
                 for source in &sources {
                     for res_id in &res_ids {
-                        source.fetch_file_sync(&locales[0], &res_id, false);
+                        source.fetch_file_sync(&locales[0], res_id, false);
                     }
                 }
             })
         });
+
+        let stats: SourceStats = sources.iter().map(FileSource::stats).sum();
+        println!("{}: {:?}", scenario.name, stats);
     }
 
     group.finish();
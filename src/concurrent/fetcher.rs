@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use fluent_fallback::types::ResourceId;
+use futures::future::join_all;
+use std::io;
+
+/// The thread-safe counterpart of
+/// [`source::FileFetcher`](../source/trait.FileFetcher.html), whose async
+/// methods return `Send` futures.
+///
+/// The built-in [`DirectoryFileFetcher`] implements both traits, as does a
+/// [`DecompressingFileFetcher`] wrapping a fetcher implementing this one.
+///
+/// [`DirectoryFileFetcher`]: ../fetchers/struct.DirectoryFileFetcher.html
+/// [`DecompressingFileFetcher`]: ../fetchers/struct.DecompressingFileFetcher.html
+#[async_trait]
+pub trait FileFetcher: Send + Sync {
+    /// Return the `String` representation for `path`. This version is
+    /// blocking.
+    ///
+    /// See [`fetch`](#tymethod.fetch).
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String>;
+
+    /// Return the `String` representation for `path`.
+    ///
+    /// See [`fetch_sync`](#tymethod.fetch_sync)
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String>;
+
+    /// Return the raw contents of `path`. This version is blocking.
+    ///
+    /// See [`source::FileFetcher::fetch_bytes_sync`](../source/trait.FileFetcher.html#method.fetch_bytes_sync).
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_sync(resource_id).map(String::into_bytes)
+    }
+
+    /// Return the raw contents of `path`.
+    ///
+    /// See [`source::FileFetcher::fetch_bytes`](../source/trait.FileFetcher.html#method.fetch_bytes).
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch(resource_id).await.map(String::into_bytes)
+    }
+
+    /// Return the raw contents of each of `resource_ids`, in the same order.
+    ///
+    /// See [`source::FileFetcher::fetch_many`](../source/trait.FileFetcher.html#method.fetch_many).
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        join_all(resource_ids.iter().map(|id| self.fetch_bytes(id))).await
+    }
+
+    /// Return a stamp which changes whenever the contents of `path` do.
+    ///
+    /// See [`source::FileFetcher::stamp`](../source/trait.FileFetcher.html#method.stamp).
+    fn stamp(&self, _resource_id: &ResourceId) -> Option<String> {
+        None
+    }
+
+    /// Describe where the last fetch of `path` was served from.
+    ///
+    /// See [`source::FileFetcher::origin`](../source/trait.FileFetcher.html#method.origin).
+    fn origin(&self, _resource_id: &ResourceId) -> Option<String> {
+        None
+    }
+}
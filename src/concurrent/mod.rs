@@ -0,0 +1,17 @@
+//! A variant of the registry which can be shared between threads.
+//!
+//! [`L10nRegistry`] and [`FileSource`] mirror the types of the
+//! [`registry`](../registry/index.html) and [`source`](../source/index.html)
+//! modules, but are built on `Arc`, `Mutex`, `RwLock` and `Send` futures, so
+//! that a single registry, along with the resources parsed by its sources,
+//! can be used from many threads. The generated bundles belong to the thread
+//! which requested them.
+mod fetcher;
+mod registry;
+mod source;
+
+pub use fetcher::FileFetcher;
+pub use registry::{BundleAdapter, GenerateBundles, GenerateBundlesSync, L10nRegistry};
+pub use source::{ArcResource, FileSource, ResourceFuture, ResourceOption, ResourceStatus};
+
+pub type FluentBundle = fluent_bundle::FluentBundle<ArcResource>;
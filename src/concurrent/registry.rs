@@ -0,0 +1,607 @@
+use super::{ArcResource, FileSource, FluentBundle, ResourceOption, ResourceStatus};
+use crate::env::ErrorReporter;
+use crate::errors::{L10nRegistryError, L10nRegistrySetupError};
+use crate::fluent::FluentError;
+use crate::registry::{PreloadReport, SourcePreloadReport};
+use crate::solver::{AsyncTester, ParallelProblemSolver, SerialProblemSolver, SyncTester};
+use crate::source::SourceStats;
+
+use fluent_fallback::generator::{BundleGenerator, BundleIterator, BundleStream};
+use fluent_fallback::types::ResourceId;
+use futures::{
+    future::join_all,
+    stream::{Collect, FuturesOrdered},
+    Stream, StreamExt,
+};
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    task::{Context, Poll},
+};
+use unic_langid::LanguageIdentifier;
+
+type BundleResult = Result<FluentBundle, (FluentBundle, Vec<FluentError>)>;
+
+/// The thread-safe counterpart of
+/// [`registry::BundleAdapter`](../registry/trait.BundleAdapter.html).
+pub trait BundleAdapter {
+    fn adapt_bundle(&self, bundle: &mut FluentBundle);
+}
+
+struct Shared<P, B> {
+    sources: RwLock<Vec<Vec<FileSource>>>,
+    provider: P,
+    bundle_adapter: Option<B>,
+}
+
+/// The thread-safe counterpart of
+/// [`registry::L10nRegistry`](../registry/struct.L10nRegistry.html).
+///
+/// Clones of a registry share their sources. Bundle generators work on the
+/// sources registered at the time they are created.
+pub struct L10nRegistry<P, B> {
+    shared: Arc<Shared<P, B>>,
+}
+
+impl<P, B> Clone for L10nRegistry<P, B> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<P, B> L10nRegistry<P, B> {
+    pub fn with_provider(provider: P) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                sources: Default::default(),
+                provider,
+                bundle_adapter: None,
+            }),
+        }
+    }
+
+    pub fn set_adapt_bundle(&mut self, bundle_adapter: B) -> Result<(), L10nRegistrySetupError>
+    where
+        B: BundleAdapter,
+    {
+        let shared =
+            Arc::get_mut(&mut self.shared).ok_or(L10nRegistrySetupError::RegistryLocked)?;
+        shared.bundle_adapter = Some(bundle_adapter);
+        Ok(())
+    }
+
+    // The sources stay consistent even if a thread panicked while holding
+    // the lock, as each setup method leaves them in a valid state.
+    fn read_sources(&self) -> RwLockReadGuard<'_, Vec<Vec<FileSource>>> {
+        self.shared
+            .sources
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_sources(&self) -> RwLockWriteGuard<'_, Vec<Vec<FileSource>>> {
+        self.shared
+            .sources
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn register_sources(
+        &self,
+        new_sources: Vec<FileSource>,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let mut sources = self.write_sources();
+        for new_source in new_sources {
+            if sources.iter().flatten().any(|source| *source == new_source) {
+                return Err(L10nRegistrySetupError::DuplicatedSource {
+                    name: new_source.name,
+                });
+            }
+            if let Some(metasource) = sources
+                .iter_mut()
+                .find(|source| source[0].metasource == new_source.metasource)
+            {
+                metasource.push(new_source);
+            } else {
+                sources.push(vec![new_source]);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update_sources(
+        &self,
+        upd_sources: Vec<FileSource>,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let mut sources = self.write_sources();
+        for upd_source in upd_sources {
+            let source = sources
+                .iter_mut()
+                .flatten()
+                .find(|source| **source == upd_source);
+            match source {
+                Some(source) => *source = upd_source,
+                None => {
+                    return Err(L10nRegistrySetupError::MissingSource {
+                        name: upd_source.name,
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_sources<S>(&self, del_sources: Vec<S>) -> Result<(), L10nRegistrySetupError>
+    where
+        S: ToString,
+    {
+        let mut sources = self.write_sources();
+        let del_sources: Vec<String> = del_sources.into_iter().map(|s| s.to_string()).collect();
+        for metasource in sources.iter_mut() {
+            metasource.retain(|source| !del_sources.contains(&source.name));
+        }
+        sources.retain(|metasource| !metasource.is_empty());
+        Ok(())
+    }
+
+    pub fn clear_sources(&self) -> Result<(), L10nRegistrySetupError> {
+        self.write_sources().clear();
+        Ok(())
+    }
+
+    pub fn get_source_names(&self) -> Result<Vec<String>, L10nRegistrySetupError> {
+        let sources = self.read_sources();
+        Ok(sources.iter().flatten().map(|s| s.name.clone()).collect())
+    }
+
+    pub fn has_source(&self, name: &str) -> Result<bool, L10nRegistrySetupError> {
+        let sources = self.read_sources();
+        Ok(sources.iter().flatten().any(|source| source.name == name))
+    }
+
+    pub fn get_source(&self, name: &str) -> Result<Option<FileSource>, L10nRegistrySetupError> {
+        let sources = self.read_sources();
+        Ok(sources
+            .iter()
+            .flatten()
+            .find(|source| source.name == name)
+            .cloned())
+    }
+
+    /// Drop the cached copies of `resource_ids` in each of `locales` from
+    /// every registered source, so that they are fetched again the next time
+    /// bundles are generated.
+    pub fn invalidate_resources(
+        &self,
+        locales: &[LanguageIdentifier],
+        resource_ids: &[ResourceId],
+    ) {
+        for source in self.read_sources().iter().flatten() {
+            for locale in locales {
+                for resource_id in resource_ids {
+                    source.invalidate(locale, resource_id);
+                }
+            }
+        }
+    }
+
+    /// Returns the sum of the [`stats`](struct.FileSource.html#method.stats)
+    /// of every registered source.
+    pub fn stats(&self) -> SourceStats {
+        self.read_sources()
+            .iter()
+            .flatten()
+            .map(FileSource::stats)
+            .sum()
+    }
+
+    /// The locales of `locales` which `source` provides, directly or through
+    /// an alias.
+    fn preload_locales<'l>(
+        source: &FileSource,
+        locales: &'l [LanguageIdentifier],
+    ) -> Vec<&'l LanguageIdentifier> {
+        locales
+            .iter()
+            .filter(|locale| source.resolve_locale(locale).is_some())
+            .collect()
+    }
+
+    /// Fetch and parse `resource_ids` in each of `locales` from every
+    /// registered source. This version is blocking.
+    /// See [`registry::L10nRegistry::preload_sync`](../registry/struct.L10nRegistry.html#method.preload_sync).
+    pub fn preload_sync(
+        &self,
+        locales: &[LanguageIdentifier],
+        resource_ids: &[ResourceId],
+    ) -> PreloadReport {
+        let sources = self
+            .read_sources()
+            .iter()
+            .flatten()
+            .map(|source| {
+                let mut report = preload_report(source);
+                for locale in Self::preload_locales(source, locales) {
+                    for resource_id in resource_ids {
+                        let option = source.fetch_file_sync(locale, resource_id, true);
+                        record_preload(&mut report, locale, resource_id, option);
+                    }
+                }
+                report
+            })
+            .collect();
+        PreloadReport { sources }
+    }
+
+    /// Fetch and parse `resource_ids` in each of `locales` from every
+    /// registered source, fetching the resources of a source and locale as
+    /// one batch.
+    /// See [`registry::L10nRegistry::preload`](../registry/struct.L10nRegistry.html#method.preload).
+    pub async fn preload(
+        &self,
+        locales: &[LanguageIdentifier],
+        resource_ids: &[ResourceId],
+    ) -> PreloadReport {
+        // Don't hold the lock of the sources across the await point.
+        let sources: Vec<FileSource> = self.read_sources().iter().flatten().cloned().collect();
+
+        let reports = sources.iter().map(|source| async move {
+            let locales = Self::preload_locales(source, locales);
+            let batches = locales
+                .iter()
+                .map(|locale| join_all(source.fetch_files(locale, resource_ids)));
+            let options = join_all(batches).await;
+
+            let mut report = preload_report(source);
+            for (locale, options) in locales.into_iter().zip(options) {
+                for (resource_id, option) in resource_ids.iter().zip(options) {
+                    record_preload(&mut report, locale, resource_id, option);
+                }
+            }
+            report
+        });
+        PreloadReport {
+            sources: join_all(reports).await,
+        }
+    }
+
+    pub fn get_available_locales(&self) -> Result<Vec<LanguageIdentifier>, L10nRegistrySetupError> {
+        let sources = self.read_sources();
+        let mut result = HashSet::new();
+        for source in sources.iter().flatten() {
+            for locale in source.locales() {
+                result.insert(locale);
+            }
+            for alias in source.options().aliases.keys() {
+                if source.resolve_locale(alias).is_some() {
+                    result.insert(alias);
+                }
+            }
+        }
+        Ok(result.into_iter().map(|l| l.to_owned()).collect())
+    }
+
+    pub fn generate_bundles_sync(
+        &self,
+        locales: std::vec::IntoIter<LanguageIdentifier>,
+        resource_ids: Vec<ResourceId>,
+    ) -> GenerateBundlesSync<P, B> {
+        GenerateBundlesSync {
+            cursor: Cursor::new(self.clone(), locales, resource_ids),
+            current: None,
+        }
+    }
+
+    pub fn generate_bundles(
+        &self,
+        locales: std::vec::IntoIter<LanguageIdentifier>,
+        resource_ids: Vec<ResourceId>,
+    ) -> GenerateBundles<P, B> {
+        GenerateBundles {
+            cursor: Cursor::new(self.clone(), locales, resource_ids),
+            current: None,
+        }
+    }
+}
+
+impl<P, B> BundleGenerator for L10nRegistry<P, B>
+where
+    P: ErrorReporter,
+    B: BundleAdapter,
+{
+    type Resource = ArcResource;
+    type Iter = GenerateBundlesSync<P, B>;
+    type Stream = GenerateBundles<P, B>;
+    type LocalesIter = std::vec::IntoIter<LanguageIdentifier>;
+
+    fn bundles_iter(
+        &self,
+        locales: Self::LocalesIter,
+        resource_ids: Vec<ResourceId>,
+    ) -> Self::Iter {
+        self.generate_bundles_sync(locales, resource_ids)
+    }
+
+    fn bundles_stream(
+        &self,
+        locales: Self::LocalesIter,
+        resource_ids: Vec<ResourceId>,
+    ) -> Self::Stream {
+        self.generate_bundles(locales, resource_ids)
+    }
+}
+
+fn preload_report(source: &FileSource) -> SourcePreloadReport {
+    SourcePreloadReport {
+        name: source.name.clone(),
+        ..Default::default()
+    }
+}
+
+/// Sort the outcome of preloading `resource_id` into `report`.
+fn record_preload(
+    report: &mut SourcePreloadReport,
+    locale: &LanguageIdentifier,
+    resource_id: &ResourceId,
+    option: ResourceOption,
+) {
+    let entry = (locale.clone(), resource_id.clone());
+    match option {
+        ResourceOption::Some(_) => report.loaded.push(entry),
+        ResourceOption::FetchFailed { kind, .. } => report.failed.push((entry.0, entry.1, kind)),
+        // Neither a sync load which overloads pending async loads, nor an
+        // awaited async load, leaves a resource pending.
+        ResourceOption::MissingOptional
+        | ResourceOption::MissingRequired
+        | ResourceOption::Pending(_) => report.missing.push(entry),
+    }
+}
+
+/// Tests the sources of one metasource for one locale.
+struct SourceTester {
+    locale: LanguageIdentifier,
+    metasource: usize,
+    /// The sources of the metasource, in the order the solver tries them.
+    sources: Vec<FileSource>,
+    resource_ids: Vec<ResourceId>,
+}
+
+impl SourceTester {
+    fn bundle_from_order<P, B>(
+        &self,
+        order: &[usize],
+        reg: &L10nRegistry<P, B>,
+    ) -> Option<BundleResult>
+    where
+        P: ErrorReporter,
+        B: BundleAdapter,
+    {
+        let mut bundle = FluentBundle::new(vec![self.locale.clone()]);
+
+        if let Some(bundle_adapter) = &reg.shared.bundle_adapter {
+            bundle_adapter.adapt_bundle(&mut bundle);
+        }
+
+        let mut errors = vec![];
+
+        for (&source_idx, resource_id) in order.iter().zip(self.resource_ids.iter()) {
+            let source = &self.sources[source_idx];
+            if let ResourceOption::Some(res) =
+                source.fetch_file_sync(&self.locale, resource_id, /* overload */ true)
+            {
+                if let Some(resolved) = source.resolve_locale(&self.locale) {
+                    if !bundle.locales.contains(resolved) {
+                        bundle.locales.push(resolved.clone());
+                    }
+                }
+                if source.options().allow_override {
+                    bundle.add_resource_overriding(res);
+                } else if let Err(err) = bundle.add_resource(res) {
+                    errors.extend(err.into_iter().map(|error| L10nRegistryError::FluentError {
+                        resource_id: resource_id.clone(),
+                        loc: None,
+                        error,
+                        origin: None,
+                    }));
+                }
+            } else if resource_id.is_required() {
+                return None;
+            }
+        }
+
+        if !errors.is_empty() {
+            reg.shared.provider.report_errors(errors);
+        }
+        Some(Ok(bundle))
+    }
+}
+
+impl SyncTester for SourceTester {
+    fn test_sync(&self, res_idx: usize, source_idx: usize) -> bool {
+        !self.sources[source_idx]
+            .fetch_file_sync(
+                &self.locale,
+                &self.resource_ids[res_idx],
+                /* overload */ true,
+            )
+            .is_required_and_missing()
+    }
+}
+
+struct TestResult(Collect<FuturesOrdered<ResourceStatus>, Vec<ResourceOption>>);
+
+impl Future for TestResult {
+    type Output = Vec<bool>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|set| set.iter().map(|c| !c.is_required_and_missing()).collect())
+    }
+}
+
+impl AsyncTester for SourceTester {
+    type Result = TestResult;
+
+    fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result {
+        let stream = query
+            .iter()
+            .map(|&(res_idx, source_idx)| {
+                self.sources[source_idx].fetch_file(&self.locale, &self.resource_ids[res_idx])
+            })
+            .collect::<FuturesOrdered<_>>();
+        TestResult(stream.collect())
+    }
+}
+
+/// The locales and metasources left to try by a bundle generator.
+struct Cursor<P, B> {
+    reg: L10nRegistry<P, B>,
+    sources: Vec<Vec<FileSource>>,
+    locales: std::vec::IntoIter<LanguageIdentifier>,
+    resource_ids: Vec<ResourceId>,
+}
+
+impl<P, B> Cursor<P, B> {
+    fn new(
+        reg: L10nRegistry<P, B>,
+        locales: std::vec::IntoIter<LanguageIdentifier>,
+        resource_ids: Vec<ResourceId>,
+    ) -> Self {
+        let sources = reg.read_sources().clone();
+        Self {
+            reg,
+            sources,
+            locales,
+            resource_ids,
+        }
+    }
+
+    fn tester(&self, locale: LanguageIdentifier, metasource: usize) -> SourceTester {
+        SourceTester {
+            locale,
+            metasource,
+            sources: self.sources[metasource].iter().rev().cloned().collect(),
+            resource_ids: self.resource_ids.clone(),
+        }
+    }
+
+    /// Returns the tester to use after `previous` has been exhausted, which
+    /// is either the next metasource for the same locale, or the last
+    /// metasource for the next locale. `missing` is the index of a resource
+    /// which none of the sources of `previous` has.
+    fn advance(
+        &mut self,
+        previous: Option<SourceTester>,
+        missing: Option<usize>,
+    ) -> Option<SourceTester>
+    where
+        P: ErrorReporter,
+    {
+        if let Some(previous) = previous {
+            if previous.metasource > 0 {
+                return Some(self.tester(previous.locale, previous.metasource - 1));
+            }
+            // Only signal an error if we run out of metasources to try.
+            if let Some(idx) = missing {
+                self.reg
+                    .shared
+                    .provider
+                    .report_errors(vec![L10nRegistryError::MissingResource {
+                        locale: previous.locale,
+                        resource_id: self.resource_ids[idx].clone(),
+                    }]);
+            }
+        }
+        let locale = self.locales.next()?;
+        let metasource = self.sources.len().checked_sub(1)?;
+        Some(self.tester(locale, metasource))
+    }
+}
+
+/// The thread-safe counterpart of
+/// [`registry::GenerateBundlesSync`](../registry/struct.GenerateBundlesSync.html).
+pub struct GenerateBundlesSync<P, B> {
+    cursor: Cursor<P, B>,
+    current: Option<(SourceTester, SerialProblemSolver)>,
+}
+
+impl<P, B> BundleIterator for GenerateBundlesSync<P, B> {}
+
+impl<P, B> Iterator for GenerateBundlesSync<P, B>
+where
+    P: ErrorReporter,
+    B: BundleAdapter,
+{
+    type Item = BundleResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let missing = match &mut self.current {
+                Some((tester, solver)) => match solver.try_next(tester, false) {
+                    Ok(Some(order)) => match tester.bundle_from_order(order, &self.cursor.reg) {
+                        Some(bundle) => return Some(bundle),
+                        None => continue,
+                    },
+                    Ok(None) => None,
+                    Err(idx) => Some(idx),
+                },
+                None => None,
+            };
+            let previous = self.current.take().map(|(tester, _)| tester);
+            let tester = self.cursor.advance(previous, missing)?;
+            let solver = SerialProblemSolver::new(tester.resource_ids.len(), tester.sources.len());
+            self.current = Some((tester, solver));
+        }
+    }
+}
+
+/// The thread-safe counterpart of
+/// [`registry::GenerateBundles`](../registry/struct.GenerateBundles.html).
+pub struct GenerateBundles<P, B> {
+    cursor: Cursor<P, B>,
+    current: Option<(SourceTester, ParallelProblemSolver<SourceTester>)>,
+}
+
+#[async_trait::async_trait(?Send)]
+impl<P, B> BundleStream for GenerateBundles<P, B> {}
+
+impl<P, B> Stream for GenerateBundles<P, B>
+where
+    P: ErrorReporter,
+    B: BundleAdapter,
+{
+    type Item = BundleResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let missing = match &mut this.current {
+                Some((tester, solver)) => match Pin::new(solver).try_poll_next(cx, tester, false) {
+                    Poll::Ready(Ok(Some(order))) => {
+                        match tester.bundle_from_order(&order, &this.cursor.reg) {
+                            Some(bundle) => return Some(bundle).into(),
+                            None => continue,
+                        }
+                    }
+                    Poll::Ready(Ok(None)) => None,
+                    Poll::Ready(Err(idx)) => Some(idx),
+                    Poll::Pending => return Poll::Pending,
+                },
+                None => None,
+            };
+            let previous = this.current.take().map(|(tester, _)| tester);
+            let tester = match this.cursor.advance(previous, missing) {
+                Some(tester) => tester,
+                None => return None.into(),
+            };
+            let solver =
+                ParallelProblemSolver::new(tester.resource_ids.len(), tester.sources.len());
+            this.current = Some((tester, solver));
+        }
+    }
+}
@@ -0,0 +1,895 @@
+use super::FileFetcher;
+use crate::env::ErrorReporter;
+use crate::errors::{L10nRegistryError, L10nRegistrySetupError};
+use crate::fluent::FluentResource;
+use crate::source::{
+    parse_fetched, CacheStatus, ChecksumManifest, FileSourceOptions, Parsed, PathTemplate,
+    ResourceCache, RetryPolicy, SourceIndex, SourceStats, StatsCounter,
+};
+
+use fluent_fallback::types::{ResourceId, ToResourceId};
+use futures::{
+    channel::oneshot,
+    future::{self, BoxFuture, Either, Shared},
+    Future, FutureExt,
+};
+use rustc_hash::FxHashMap;
+use std::{
+    borrow::Borrow,
+    fmt, io,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, TryLockError},
+    task::Poll,
+    time::Instant,
+};
+use unic_langid::LanguageIdentifier;
+
+pub type ArcResource = Arc<FluentResource>;
+
+/// The thread-safe counterpart of
+/// [`source::ResourceOption`](../source/enum.ResourceOption.html).
+#[derive(Clone, Debug)]
+pub enum ResourceOption {
+    MissingOptional,
+    MissingRequired,
+    Some(ArcResource),
+    /// A resource which exists, but couldn't be fetched. This is treated as
+    /// missing, but the error has been reported as a
+    /// [`L10nRegistryError::FetchError`](../errors/enum.L10nRegistryError.html#variant.FetchError).
+    FetchFailed {
+        required: bool,
+        kind: io::ErrorKind,
+    },
+    /// A resource which is being loaded asynchronously, returned by a sync
+    /// load which isn't allowed to overload it.
+    Pending(ResourceFuture),
+}
+
+impl ResourceOption {
+    pub fn missing_resource(resource_id: &ResourceId) -> Self {
+        if resource_id.is_required() {
+            Self::MissingRequired
+        } else {
+            Self::MissingOptional
+        }
+    }
+
+    pub fn fetch_failed(resource_id: &ResourceId, kind: io::ErrorKind) -> Self {
+        Self::FetchFailed {
+            required: resource_id.is_required(),
+            kind,
+        }
+    }
+
+    pub fn is_some(&self) -> bool {
+        matches!(self, Self::Some(_))
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingOptional | Self::MissingRequired | Self::FetchFailed { .. }
+        )
+    }
+
+    pub fn is_required_and_missing(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingRequired | Self::FetchFailed { required: true, .. }
+        )
+    }
+
+    pub fn is_fetch_failed(&self) -> bool {
+        matches!(self, Self::FetchFailed { .. })
+    }
+
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending(_))
+    }
+}
+
+impl From<ResourceOption> for Option<ArcResource> {
+    fn from(other: ResourceOption) -> Self {
+        match other {
+            ResourceOption::Some(res) => Some(res),
+            _ => None,
+        }
+    }
+}
+
+pub type ResourceFuture = Shared<BoxFuture<'static, ResourceOption>>;
+
+/// The thread-safe counterpart of
+/// [`source::ResourceStatus`](../source/enum.ResourceStatus.html).
+#[derive(Clone)]
+pub enum ResourceStatus {
+    MissingRequired,
+    MissingOptional,
+    FetchFailed { required: bool, kind: io::ErrorKind },
+    Loading(ResourceFuture),
+    Loaded(ArcResource),
+}
+
+impl ResourceStatus {
+    /// Match a cached miss or failure to whether `resource_id` is required,
+    /// since the cache entry may have been created by a request of the other
+    /// type.
+    fn for_resource(self, resource_id: &ResourceId) -> Self {
+        match self {
+            Self::MissingRequired | Self::MissingOptional => {
+                ResourceOption::missing_resource(resource_id).into()
+            }
+            Self::FetchFailed { kind, .. } => {
+                ResourceOption::fetch_failed(resource_id, kind).into()
+            }
+            status => status,
+        }
+    }
+}
+
+impl From<ResourceOption> for ResourceStatus {
+    fn from(input: ResourceOption) -> Self {
+        match input {
+            ResourceOption::Some(res) => Self::Loaded(res),
+            ResourceOption::MissingOptional => Self::MissingOptional,
+            ResourceOption::MissingRequired => Self::MissingRequired,
+            ResourceOption::FetchFailed { required, kind } => Self::FetchFailed { required, kind },
+            ResourceOption::Pending(future) => Self::Loading(future),
+        }
+    }
+}
+
+impl From<ResourceStatus> for ResourceOption {
+    fn from(input: ResourceStatus) -> Self {
+        match input {
+            ResourceStatus::Loaded(res) => Self::Some(res),
+            ResourceStatus::MissingOptional => Self::MissingOptional,
+            ResourceStatus::MissingRequired => Self::MissingRequired,
+            ResourceStatus::FetchFailed { required, kind } => Self::FetchFailed { required, kind },
+            ResourceStatus::Loading(future) => Self::Pending(future),
+        }
+    }
+}
+
+impl Future for ResourceStatus {
+    type Output = ResourceOption;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        match &mut *self {
+            Self::MissingRequired => ResourceOption::MissingRequired.into(),
+            Self::MissingOptional => ResourceOption::MissingOptional.into(),
+            Self::FetchFailed { required, kind } => ResourceOption::FetchFailed {
+                required: *required,
+                kind: *kind,
+            }
+            .into(),
+            Self::Loaded(res) => ResourceOption::Some(res.clone()).into(),
+            Self::Loading(res) => Pin::new(res).poll(cx),
+        }
+    }
+}
+
+impl CacheStatus for ResourceStatus {
+    type Output = ResourceOption;
+    type Future = BoxFuture<'static, ResourceOption>;
+
+    fn as_loading(&self) -> Option<&ResourceFuture> {
+        match self {
+            Self::Loading(future) => Some(future),
+            _ => None,
+        }
+    }
+
+    fn loading(future: ResourceFuture) -> Self {
+        Self::Loading(future)
+    }
+
+    fn loaded_size(&self) -> Option<usize> {
+        match self {
+            Self::Loaded(res) => Some(res.source().len()),
+            _ => None,
+        }
+    }
+}
+
+/// The thread-safe counterpart of
+/// [`source::FileSource`](../source/struct.FileSource.html).
+///
+/// Clones of a `FileSource` share their cache, also across threads. Of the
+/// [`FileSourceOptions`](../source/struct.FileSourceOptions.html), the
+/// `fetch_timeout`, `retry_policy` and `record_stamps`, which rely on a
+/// timer or a startup cache, aren't supported yet: creating a source with
+/// any of them set fails with an
+/// [`UnsupportedOption`](../errors/enum.L10nRegistrySetupError.html#variant.UnsupportedOption)
+/// error.
+#[derive(Clone)]
+pub struct FileSource {
+    pub name: String,
+    pub metasource: String,
+    pre_path: String,
+    options: FileSourceOptions,
+    locales: Vec<LanguageIdentifier>,
+    template: Arc<PathTemplate>,
+    prefixes: Arc<Vec<String>>,
+    index: Option<Arc<SourceIndex>>,
+    shared: Arc<Inner>,
+}
+
+struct Inner {
+    fetcher: Box<dyn FileFetcher>,
+    error_reporter: Option<Box<dyn ErrorReporter + Send + Sync>>,
+    entries: Mutex<ResourceCache<ResourceStatus>>,
+    /// Senders allowing a sync load to resolve the pending async load of the
+    /// same resource, keyed by the id of its cache entry.
+    pending: Mutex<FxHashMap<u64, oneshot::Sender<ResourceOption>>>,
+    stats: StatsCounter,
+    checksums: Option<Arc<ChecksumManifest>>,
+}
+
+impl fmt::Display for FileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Debug for FileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("FileSource");
+        debug
+            .field("name", &self.name)
+            .field("metasource", &self.metasource)
+            .field("locales", &self.locales)
+            .field("pre_path", &self.pre_path);
+        if let Some(index) = self.get_index() {
+            debug.field("index", index);
+        }
+        debug.finish()
+    }
+}
+
+impl PartialEq<FileSource> for FileSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.metasource == other.metasource
+    }
+}
+
+impl Eq for FileSource {}
+
+impl FileSource {
+    /// Create a `FileSource` using the provided
+    /// [`FileFetcher`](trait.FileFetcher.html).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pre_path` contains an unknown placeholder, or if the
+    /// `options` aren't supported. Use [`try_new`](#method.try_new) to
+    /// handle the error instead.
+    pub fn new(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+    ) -> Self {
+        Self::try_new(name, metasource, locales, pre_path, options, fetcher).unwrap()
+    }
+
+    /// Create a `FileSource`, or return an error if `pre_path` contains an
+    /// unknown placeholder or if the `options` aren't supported.
+    pub fn try_new(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        Self::with_optional_index(name, metasource, locales, pre_path, options, fetcher, None)
+    }
+
+    /// Create a `FileSource` with the list of full paths available in it.
+    /// See [`source::FileSource::new_with_index`](../source/struct.FileSource.html#method.new_with_index).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pre_path` contains an unknown placeholder, or if the
+    /// `options` aren't supported. Use
+    /// [`try_new_with_index`](#method.try_new_with_index) to handle the
+    /// error instead.
+    pub fn new_with_index(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+        index: Vec<String>,
+    ) -> Self {
+        Self::try_new_with_index(name, metasource, locales, pre_path, options, fetcher, index)
+            .unwrap()
+    }
+
+    /// Create a `FileSource` with the list of full paths available in it,
+    /// or return an error if `pre_path` contains an unknown placeholder or
+    /// if the `options` aren't supported.
+    pub fn try_new_with_index(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+        index: Vec<String>,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        Self::with_optional_index(
+            name,
+            metasource,
+            locales,
+            pre_path,
+            options,
+            fetcher,
+            Some(index),
+        )
+    }
+
+    fn with_optional_index(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+        index: Option<Vec<String>>,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        options.check_features(&name)?;
+        let unsupported = [
+            ("fetch_timeout", options.fetch_timeout.is_some()),
+            (
+                "retry_policy",
+                options.retry_policy != RetryPolicy::default(),
+            ),
+            ("record_stamps", options.record_stamps),
+        ];
+        if let Some((option, _)) = unsupported.iter().find(|(_, set)| *set) {
+            return Err(L10nRegistrySetupError::UnsupportedOption {
+                name,
+                option: option.to_string(),
+            });
+        }
+        let template = PathTemplate::new(&pre_path, &options.variables)?;
+        let prefixes: Vec<String> = locales
+            .iter()
+            .map(|locale| template.expand(locale))
+            .collect();
+        let index = index.map(|index| Arc::new(SourceIndex::new(index, &prefixes)));
+        let shared = Arc::new(Inner {
+            fetcher: Box::new(fetcher),
+            error_reporter: None,
+            entries: Mutex::new(ResourceCache::new(options.cache_policy.clone())),
+            pending: Mutex::new(FxHashMap::default()),
+            stats: StatsCounter::default(),
+            checksums: options.checksums.clone(),
+        });
+        Ok(Self {
+            name,
+            metasource: metasource.unwrap_or_default(),
+            pre_path,
+            options,
+            locales,
+            template: Arc::new(template),
+            prefixes: Arc::new(prefixes),
+            index,
+            shared,
+        })
+    }
+
+    pub fn set_reporter(&mut self, reporter: impl ErrorReporter + Send + Sync + 'static) {
+        let shared = Arc::get_mut(&mut self.shared).unwrap();
+        shared.error_reporter = Some(Box::new(reporter));
+    }
+
+    /// Returns a snapshot of the counters of the source, shared by all of
+    /// its clones.
+    pub fn stats(&self) -> SourceStats {
+        self.shared.stats.snapshot()
+    }
+
+    pub fn locales(&self) -> &[LanguageIdentifier] {
+        &self.locales
+    }
+
+    pub fn pre_path(&self) -> &str {
+        &self.pre_path
+    }
+
+    pub fn options(&self) -> &FileSourceOptions {
+        &self.options
+    }
+
+    pub fn get_index(&self) -> Option<&Vec<String>> {
+        self.index.as_ref().map(|index| index.paths())
+    }
+
+    /// See [`source::FileSource::resolve_locale`](../source/struct.FileSource.html#method.resolve_locale).
+    pub fn resolve_locale(&self, locale: &LanguageIdentifier) -> Option<&LanguageIdentifier> {
+        self.locale_idx(locale).map(|idx| &self.locales[idx])
+    }
+
+    fn locale_idx(&self, locale: &LanguageIdentifier) -> Option<usize> {
+        self.locales.iter().position(|l| l == locale).or_else(|| {
+            let target = self.options.aliases.get(locale)?;
+            self.locales.iter().position(|l| l == target)
+        })
+    }
+
+    fn get_path(&self, locale: &LanguageIdentifier, resource_id: &ResourceId) -> ResourceId {
+        let prefix = match self.locale_idx(locale) {
+            Some(locale_idx) => self.prefixes[locale_idx].clone(),
+            None => self.template.expand(locale),
+        };
+        (prefix + &resource_id.value).to_resource_id(resource_id.resource_type)
+    }
+
+    /// Synchronously fetch the resource for the combination of `locale` and
+    /// `path`.
+    ///
+    /// If the resource is being loaded asynchronously, `overload` decides
+    /// whether to load it synchronously anyway, handing the result over to
+    /// the pending async load, which doesn't fetch it again. Otherwise, a
+    /// [`L10nRegistryError::PendingAsyncLoad`] is reported and
+    /// [`ResourceOption::Pending`] is returned, which the caller may block on
+    /// with an executor of its choice.
+    ///
+    /// [`L10nRegistryError::PendingAsyncLoad`]: ../errors/enum.L10nRegistryError.html#variant.PendingAsyncLoad
+    pub fn fetch_file_sync(
+        &self,
+        locale: &LanguageIdentifier,
+        resource_id: &ResourceId,
+        overload: bool,
+    ) -> ResourceOption {
+        if !self.may_have_file(locale, resource_id) {
+            return ResourceOption::missing_resource(resource_id);
+        }
+
+        let full_path_id = self.get_path(locale, resource_id);
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
+        let cached = self
+            .shared
+            .entries()
+            .touch(&full_path_id.value)
+            .map(|status| status.for_resource(resource_id));
+        self.shared.count_lookup(cached.is_none());
+        match cached {
+            Some(ResourceStatus::Loading(future)) if !overload => {
+                self.shared
+                    .report_errors(vec![L10nRegistryError::PendingAsyncLoad {
+                        locale: locale.clone(),
+                        resource_id: resource_id.clone(),
+                    }]);
+                return ResourceOption::Pending(future);
+            }
+            Some(ResourceStatus::Loading(_)) | None => {}
+            Some(status) => return status.into(),
+        }
+
+        // The cache isn't locked while fetching, so that other threads can use
+        // it in the meantime. Threads racing for the same resource may both
+        // fetch it, but end up sharing the result stored first.
+        let resource = self.shared.fetch_sync(locale, &full_path_id);
+        match self.shared.store(full_path_id.value, locale, resource) {
+            ResourceOption::MissingRequired | ResourceOption::MissingOptional => {
+                ResourceOption::missing_resource(resource_id)
+            }
+            ResourceOption::FetchFailed { kind, .. } => {
+                ResourceOption::fetch_failed(resource_id, kind)
+            }
+            resource => resource,
+        }
+    }
+
+    /// Fetch the resource for the combination of `locale` and `path`.
+    /// Returns a [`ResourceStatus`](enum.ResourceStatus.html) which is a
+    /// `Future` that can be polled from any thread.
+    pub fn fetch_file(
+        &self,
+        locale: &LanguageIdentifier,
+        resource_id: &ResourceId,
+    ) -> ResourceStatus {
+        if !self.may_have_file(locale, resource_id) {
+            return ResourceOption::missing_resource(resource_id).into();
+        }
+
+        let full_path_id = self.get_path(locale, resource_id);
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
+        self.shared
+            .lookup(full_path_id.value.clone(), locale, |entry_id| {
+                let read = read_resource(full_path_id.clone(), locale.clone(), self.shared.clone());
+                self.start_loading(full_path_id, entry_id, read)
+            })
+            .for_resource(resource_id)
+    }
+
+    /// Fetch several resources for `locale`, requesting the ones which are
+    /// neither cached nor being loaded with a single
+    /// [`FileFetcher::fetch_many`](trait.FileFetcher.html#method.fetch_many)
+    /// call.
+    /// See [`source::FileSource::fetch_files`](../source/struct.FileSource.html#method.fetch_files).
+    pub fn fetch_files(
+        &self,
+        locale: &LanguageIdentifier,
+        resource_ids: &[ResourceId],
+    ) -> Vec<ResourceStatus> {
+        let full_path_ids: Vec<Option<ResourceId>> = resource_ids
+            .iter()
+            .map(|resource_id| {
+                if !self.may_have_file(locale, resource_id) {
+                    None
+                } else {
+                    Some(self.get_path(locale, resource_id))
+                }
+            })
+            .collect();
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
+
+        let mut batch = FxHashMap::default();
+        let mut batch_ids = vec![];
+        {
+            let entries = self.shared.entries();
+            for full_path_id in full_path_ids.iter().flatten() {
+                if entries.get(&full_path_id.value).is_none()
+                    && !batch.contains_key(&full_path_id.value)
+                {
+                    batch.insert(full_path_id.value.clone(), batch_ids.len());
+                    batch_ids.push(full_path_id.clone());
+                }
+            }
+        }
+        let read_batch = if batch_ids.len() > 1 {
+            Some(
+                read_resources(batch_ids, locale.clone(), self.shared.clone())
+                    .boxed()
+                    .shared(),
+            )
+        } else {
+            None
+        };
+
+        resource_ids
+            .iter()
+            .zip(full_path_ids)
+            .map(|(resource_id, full_path_id)| {
+                let full_path_id = match full_path_id {
+                    Some(full_path_id) => full_path_id,
+                    None => return ResourceOption::missing_resource(resource_id).into(),
+                };
+                self.shared
+                    .lookup(full_path_id.value.clone(), locale, |entry_id| {
+                        match (batch.get(&full_path_id.value), &read_batch) {
+                            (Some(&idx), Some(read_batch)) => {
+                                let read = read_batch
+                                    .clone()
+                                    .map(move |resources| resources[idx].clone());
+                                self.start_loading(full_path_id, entry_id, read)
+                            }
+                            _ => {
+                                let read = read_resource(
+                                    full_path_id.clone(),
+                                    locale.clone(),
+                                    self.shared.clone(),
+                                );
+                                self.start_loading(full_path_id, entry_id, read)
+                            }
+                        }
+                    })
+                    .for_resource(resource_id)
+            })
+            .collect()
+    }
+
+    /// Create the `Loading` status of the cache entry `entry_id`, resolved by
+    /// `read` unless a sync load of the same resource completes first.
+    ///
+    /// The cache only keeps a weak reference to the returned future, so if
+    /// all of its clones are dropped before it completes, the load is
+    /// cancelled, allowing the resource to be fetched again.
+    fn start_loading(
+        &self,
+        full_path_id: ResourceId,
+        entry_id: u64,
+        read: impl Future<Output = ResourceOption> + Send + 'static,
+    ) -> ResourceStatus {
+        let (sender, receiver) = oneshot::channel();
+        self.shared.pending().insert(entry_id, sender);
+        let guard = LoadGuard {
+            full_path: full_path_id.value,
+            entry_id,
+            shared: self.shared.clone(),
+        };
+        ResourceStatus::Loading(load_resource(guard, receiver, read).boxed().shared())
+    }
+
+    /// Determine if the `FileSource` has a loaded resource for the combination
+    /// of `locale` and `path`. Returns `Some(true)` if the file is loaded, else
+    /// `Some(false)`. `None` is returned if the file hasn't been fetched yet,
+    /// or if its async fetch is still pending.
+    pub fn has_file<L: Borrow<LanguageIdentifier>>(
+        &self,
+        locale: L,
+        path: &ResourceId,
+    ) -> Option<bool> {
+        let locale = locale.borrow();
+        let locale_idx = match self.locale_idx(locale) {
+            Some(locale_idx) => locale_idx,
+            None => return Some(false),
+        };
+        if let Some(index) = &self.index {
+            return Some(index.contains(locale_idx, &path.value));
+        }
+        match self
+            .shared
+            .entries()
+            .get(&self.get_path(locale, path).value)
+        {
+            Some(ResourceStatus::MissingRequired)
+            | Some(ResourceStatus::MissingOptional)
+            | Some(ResourceStatus::FetchFailed { .. }) => Some(false),
+            Some(ResourceStatus::Loaded(_)) => Some(true),
+            Some(ResourceStatus::Loading(_)) | None => None,
+        }
+    }
+
+    /// Returns `false` if the resource is known not to exist without looking
+    /// at the cache, i.e. the locale isn't served by this source or the index
+    /// doesn't list the resource.
+    fn may_have_file(&self, locale: &LanguageIdentifier, path: &ResourceId) -> bool {
+        match self.locale_idx(locale) {
+            Some(locale_idx) => self
+                .index
+                .as_ref()
+                .is_none_or(|index| index.contains(locale_idx, &path.value)),
+            None => false,
+        }
+    }
+
+    /// Drop the cached resource for the combination of `locale` and `path`,
+    /// so that the next request fetches it again.
+    /// See [`source::FileSource::invalidate`](../source/struct.FileSource.html#method.invalidate).
+    pub fn invalidate(&self, locale: &LanguageIdentifier, path: &ResourceId) {
+        self.invalidate_path(&self.get_path(locale, path).value);
+    }
+
+    /// Drop the cached resource stored under `full_path`, i.e. the path
+    /// passed to the [`FileFetcher`](trait.FileFetcher.html).
+    pub fn invalidate_path(&self, full_path: &str) {
+        self.shared.entries().remove(full_path);
+    }
+
+    /// Drop all cached resources for `locale`.
+    pub fn invalidate_locale(&self, locale: &LanguageIdentifier) {
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
+        self.shared.entries().remove_locale(locale);
+    }
+
+    /// Drop all cached resources.
+    pub fn clear_cache(&self) {
+        self.shared.entries().clear();
+    }
+
+    /// Returns the total length of the sources of all resources currently
+    /// held in the cache.
+    pub fn cached_bytes(&self) -> usize {
+        self.shared.entries().loaded_bytes()
+    }
+}
+
+impl Inner {
+    fn entries(&self) -> MutexGuard<'_, ResourceCache<ResourceStatus>> {
+        lock(&self.entries)
+    }
+
+    fn pending(&self) -> MutexGuard<'_, FxHashMap<u64, oneshot::Sender<ResourceOption>>> {
+        lock(&self.pending)
+    }
+
+    fn count_lookup(&self, miss: bool) {
+        self.stats.update(|stats| {
+            if miss {
+                stats.cache_misses += 1;
+            } else {
+                stats.cache_hits += 1;
+            }
+        });
+    }
+
+    /// Return the cached status of `full_path`, calling `f` with the id of
+    /// the new entry to create it if there is none.
+    fn lookup<F>(&self, full_path: String, locale: &LanguageIdentifier, f: F) -> ResourceStatus
+    where
+        F: FnOnce(u64) -> ResourceStatus,
+    {
+        let mut miss = false;
+        let status = self.entries().lookup(full_path, locale, |entry_id| {
+            miss = true;
+            f(entry_id)
+        });
+        self.count_lookup(miss);
+        status
+    }
+
+    /// Store the result of a sync load. A pending async load of the resource
+    /// is completed with it, while a result stored by another thread in the
+    /// meantime is returned instead. A timed out fetch isn't cached, so that
+    /// the next request tries again.
+    fn store(
+        &self,
+        full_path: String,
+        locale: &LanguageIdentifier,
+        resource: ResourceOption,
+    ) -> ResourceOption {
+        let timed_out = is_timed_out(&resource);
+        let mut entries = self.entries();
+        if let Some(entry_id) = entries.loading_id(&full_path) {
+            if timed_out {
+                entries.remove(&full_path);
+            } else {
+                entries.update(&full_path, entry_id, resource.clone().into());
+            }
+            drop(entries);
+            let sender = self.pending().remove(&entry_id);
+            if let Some(sender) = sender {
+                let _ = sender.send(resource.clone());
+            }
+            return resource;
+        }
+        if timed_out {
+            return resource;
+        }
+        match entries.lookup(full_path, locale, |_| resource.clone().into()) {
+            // Loads in progress have been completed above.
+            ResourceStatus::Loading(_) => resource,
+            status => status.into(),
+        }
+    }
+
+    /// Store the result of the async load of the cache entry `entry_id`.
+    fn update(&self, full_path: &str, entry_id: u64, resource: &ResourceOption) {
+        let mut entries = self.entries();
+        if !is_timed_out(resource) {
+            entries.update(full_path, entry_id, resource.clone().into());
+        } else if entries.loading_id(full_path) == Some(entry_id) {
+            entries.remove(full_path);
+        }
+    }
+
+    fn fetch_sync(&self, locale: &LanguageIdentifier, resource_id: &ResourceId) -> ResourceOption {
+        let source = self
+            .stats
+            .time_fetch(1, || self.fetcher.fetch_bytes_sync(resource_id));
+        self.parse_resource(locale, resource_id, source)
+    }
+
+    fn report_errors(&self, errors: Vec<L10nRegistryError>) {
+        if let Some(reporter) = &self.error_reporter {
+            reporter.report_errors(errors);
+        }
+    }
+
+    /// Parse the result of fetching `resource_id`, reporting any errors.
+    /// See `source::parse_fetched`.
+    fn parse_resource(
+        &self,
+        locale: &LanguageIdentifier,
+        resource_id: &ResourceId,
+        source: io::Result<Vec<u8>>,
+    ) -> ResourceOption {
+        let parsed = parse_fetched(
+            locale,
+            resource_id,
+            source,
+            &self.stats,
+            self.checksums.as_deref(),
+            &|| self.fetcher.origin(resource_id),
+            &|errors| self.report_errors(errors),
+        );
+        match parsed {
+            Parsed::Missing => ResourceOption::missing_resource(resource_id),
+            Parsed::Failed(kind) => ResourceOption::fetch_failed(resource_id, kind),
+            Parsed::Resource(res) => ResourceOption::Some(Arc::new(res)),
+        }
+    }
+}
+
+fn is_timed_out(resource: &ResourceOption) -> bool {
+    matches!(
+        resource,
+        ResourceOption::FetchFailed {
+            kind: io::ErrorKind::TimedOut,
+            ..
+        }
+    )
+}
+
+// The cache and the pending loads stay consistent even if a thread panicked
+// while holding their lock, as every update is a single operation.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Cleans up after the load of a cache entry when it is dropped, removing
+/// the entry if the load didn't complete.
+struct LoadGuard {
+    full_path: String,
+    entry_id: u64,
+    shared: Arc<Inner>,
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        // The pending loads are never locked while a future is dropped.
+        self.shared.pending().remove(&self.entry_id);
+        // The last future of a load may be dropped by a thread holding the
+        // lock of the cache. The abandoned entry is then replaced on its next
+        // lookup instead.
+        let mut entries = match self.shared.entries.try_lock() {
+            Ok(entries) => entries,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        if entries.loading_id(&self.full_path) == Some(self.entry_id) {
+            entries.remove(&self.full_path);
+        }
+    }
+}
+
+/// Resolve with the result of a sync load sent through `sync_result`, if
+/// there is one, and otherwise with the result of `read`, storing it in the
+/// cache entry of `guard`.
+async fn load_resource(
+    guard: LoadGuard,
+    sync_result: oneshot::Receiver<ResourceOption>,
+    read: impl Future<Output = ResourceOption>,
+) -> ResourceOption {
+    futures::pin_mut!(read);
+    // `select` polls the receiver first, so a sync load completed before this
+    // future is first polled prevents the async fetch from starting at all.
+    let resource = match future::select(sync_result, read).await {
+        // The sync load has already updated the cache.
+        Either::Left((Ok(resource), _)) => return resource,
+        Either::Left((Err(oneshot::Canceled), read)) => read.await,
+        Either::Right((resource, _)) => resource,
+    };
+    guard
+        .shared
+        .update(&guard.full_path, guard.entry_id, &resource);
+    resource
+}
+
+async fn read_resource(
+    resource_id: ResourceId,
+    locale: LanguageIdentifier,
+    shared: Arc<Inner>,
+) -> ResourceOption {
+    let start = Instant::now();
+    let source = shared.fetcher.fetch_bytes(&resource_id).await;
+    shared.stats.record_fetch(1, start);
+    shared.parse_resource(&locale, &resource_id, source)
+}
+
+async fn read_resources(
+    resource_ids: Vec<ResourceId>,
+    locale: LanguageIdentifier,
+    shared: Arc<Inner>,
+) -> Arc<Vec<ResourceOption>> {
+    let start = Instant::now();
+    let sources = shared.fetcher.fetch_many(&resource_ids).await;
+    shared.stats.record_fetch(resource_ids.len() as u64, start);
+    let resources = resource_ids
+        .iter()
+        .zip(sources)
+        .map(|(resource_id, source)| shared.parse_resource(&locale, resource_id, source))
+        .collect();
+    Arc::new(resources)
+}
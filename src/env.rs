@@ -1,5 +1,13 @@
 use crate::errors::L10nRegistryError;
+use std::{future::Future, pin::Pin, time::Duration};
 
 pub trait ErrorReporter {
     fn report_errors(&self, errors: Vec<L10nRegistryError>);
 }
+
+/// Provides the delays `FileSource` needs to time out and retry async
+/// fetches, leaving the choice of the executor to the user.
+pub trait Timer {
+    /// Return a future which completes after `duration`.
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>>;
+}
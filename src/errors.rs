@@ -1,6 +1,7 @@
 use fluent_bundle::FluentError;
 use fluent_fallback::types::ResourceId;
 use std::error::Error;
+use std::io;
 use unic_langid::LanguageIdentifier;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -9,11 +10,39 @@ pub enum L10nRegistryError {
         resource_id: ResourceId,
         loc: Option<(usize, usize)>,
         error: FluentError,
+        /// Where the resource was served from, see `FileFetcher::origin`.
+        origin: Option<String>,
     },
     MissingResource {
         locale: LanguageIdentifier,
         resource_id: ResourceId,
     },
+    PendingAsyncLoad {
+        locale: LanguageIdentifier,
+        resource_id: ResourceId,
+    },
+    FetchError {
+        resource_id: ResourceId,
+        locale: LanguageIdentifier,
+        kind: io::ErrorKind,
+        message: String,
+        /// Where the resource was served from, see `FileFetcher::origin`.
+        origin: Option<String>,
+    },
+    InvalidEncoding {
+        resource_id: ResourceId,
+        locale: LanguageIdentifier,
+        valid_up_to: usize,
+    },
+    /// The SHA-256 hash of a resource doesn't match the one in the checksum
+    /// manifest of its source, which is `None` if the resource isn't listed.
+    /// Both hashes are hex-encoded.
+    ChecksumMismatch {
+        resource_id: ResourceId,
+        locale: LanguageIdentifier,
+        expected: Option<String>,
+        actual: String,
+    },
 }
 
 impl std::fmt::Display for L10nRegistryError {
@@ -29,32 +58,123 @@ impl std::fmt::Display for L10nRegistryError {
                     locale, resource_id.value
                 )
             }
+            Self::PendingAsyncLoad {
+                locale,
+                resource_id,
+            } => {
+                write!(
+                    f,
+                    "Attempted to synchronously load resource in locale {} while it's being loaded asynchronously: {}",
+                    locale, resource_id.value
+                )
+            }
+            Self::FetchError {
+                resource_id,
+                locale,
+                message,
+                origin,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Failed to fetch resource in locale {}: {}: {}",
+                    locale, resource_id.value, message
+                )?;
+                fmt_origin(f, origin)
+            }
+            Self::InvalidEncoding {
+                resource_id,
+                locale,
+                valid_up_to,
+            } => {
+                write!(
+                    f,
+                    "Invalid UTF-8 in resource in locale {} after byte {}: {}",
+                    locale, valid_up_to, resource_id.value
+                )
+            }
+            Self::ChecksumMismatch {
+                resource_id,
+                locale,
+                expected: Some(expected),
+                actual,
+            } => {
+                write!(
+                    f,
+                    "Checksum mismatch for resource in locale {}: {}: expected {}, got {}",
+                    locale, resource_id.value, expected, actual
+                )
+            }
+            Self::ChecksumMismatch {
+                resource_id,
+                locale,
+                expected: None,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Resource in locale {} is not listed in the checksum manifest: {}",
+                    locale, resource_id.value
+                )
+            }
             Self::FluentError {
                 resource_id,
                 loc,
                 error,
+                origin,
             } => {
                 if let Some(loc) = loc {
                     write!(
                         f,
                         "Fluent Error in {}[line: {}, col: {}]: {}",
                         resource_id.value, loc.0, loc.1, error
-                    )
+                    )?;
                 } else {
-                    write!(f, "Fluent Error in {}: {}", resource_id.value, error)
+                    write!(f, "Fluent Error in {}: {}", resource_id.value, error)?;
                 }
+                fmt_origin(f, origin)
             }
         }
     }
 }
 
+fn fmt_origin(f: &mut std::fmt::Formatter<'_>, origin: &Option<String>) -> std::fmt::Result {
+    match origin {
+        Some(origin) => write!(f, " (served by {})", origin),
+        None => Ok(()),
+    }
+}
+
 impl Error for L10nRegistryError {}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum L10nRegistrySetupError {
     RegistryLocked,
-    DuplicatedSource { name: String },
-    MissingSource { name: String },
+    DuplicatedSource {
+        name: String,
+    },
+    MissingSource {
+        name: String,
+    },
+    UnknownPlaceholder {
+        pre_path: String,
+        placeholder: String,
+    },
+    MissingTimer {
+        name: String,
+    },
+    /// The options of a source set `option`, which it can't honor, e.g.
+    /// because the crate feature implementing it isn't enabled.
+    UnsupportedOption {
+        name: String,
+        option: String,
+    },
+    InvalidSignature,
+    InvalidManifest {
+        reason: String,
+    },
+    /// The bytes of a trusted key aren't a valid ed25519 public key.
+    InvalidKey,
 }
 
 impl std::fmt::Display for L10nRegistrySetupError {
@@ -67,6 +187,27 @@ impl std::fmt::Display for L10nRegistrySetupError {
             Self::MissingSource { name } => {
                 write!(f, "Cannot find a source with a name {}.", &name)
             }
+            Self::UnknownPlaceholder {
+                pre_path,
+                placeholder,
+            } => {
+                write!(
+                    f,
+                    "Unknown placeholder {{{}}} in {}.",
+                    placeholder, pre_path
+                )
+            }
+            Self::MissingTimer { name } => {
+                write!(f, "Source {} has a fetch timeout but no timer.", name)
+            }
+            Self::UnsupportedOption { name, option } => {
+                write!(f, "Source {} sets the unsupported option {}.", name, option)
+            }
+            Self::InvalidSignature => {
+                write!(f, "The manifest isn't signed by any of the trusted keys.")
+            }
+            Self::InvalidManifest { reason } => write!(f, "Invalid signed manifest: {}.", reason),
+            Self::InvalidKey => write!(f, "Invalid ed25519 public key."),
         }
     }
 }
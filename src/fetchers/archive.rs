@@ -0,0 +1,147 @@
+use crate::errors::L10nRegistrySetupError;
+use crate::source::{FileFetcher, FileSource, FileSourceOptions, ResourceId};
+use async_trait::async_trait;
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, Cursor, Read, Seek},
+    path::Path,
+};
+use unic_langid::LanguageIdentifier;
+use zip::{result::ZipError, ZipArchive};
+
+/// A [`FileFetcher`] serving resources out of a zip (or JAR/omni.ja) archive.
+///
+/// The archive's central directory is read once, when the fetcher is
+/// created, and every subsequent fetch looks the entry up by the `value` of
+/// its [`ResourceId`]. As with a `DirectoryFileFetcher`, a single leading `/`
+/// is relative to the root of the archive. Entries are decompressed on
+/// demand.
+///
+/// Reading from the archive is blocking, so the asynchronous fetch resolves
+/// immediately with the result of a synchronous read.
+///
+/// [`FileFetcher`]: ../source/trait.FileFetcher.html
+pub struct ArchiveFileFetcher<R> {
+    archive: RefCell<ZipArchive<R>>,
+}
+
+impl ArchiveFileFetcher<File> {
+    /// Open the archive at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl ArchiveFileFetcher<Cursor<Vec<u8>>> {
+    /// Use an archive which has already been loaded into memory.
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        Self::new(Cursor::new(bytes))
+    }
+}
+
+impl<R: Read + Seek> ArchiveFileFetcher<R> {
+    pub fn new(reader: R) -> io::Result<Self> {
+        let archive = ZipArchive::new(reader).map_err(zip_to_io_error)?;
+        Ok(Self {
+            archive: RefCell::new(archive),
+        })
+    }
+
+    /// Returns the paths of all files stored in the archive, in the format
+    /// expected by [`FileSource::new_with_index`].
+    ///
+    /// [`FileSource::new_with_index`]: ../source/struct.FileSource.html#method.new_with_index
+    pub fn index(&self) -> Vec<String> {
+        self.archive
+            .borrow()
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| name.to_string())
+            .collect()
+    }
+}
+
+impl<R: Read + Seek + 'static> ArchiveFileFetcher<R> {
+    /// Create an indexed [`FileSource`] reading from this archive.
+    ///
+    /// The index is built from the archive listing, so resources absent
+    /// from the archive are known to be missing without probing it. Returns
+    /// an error if `pre_path` contains an unknown placeholder.
+    ///
+    /// [`FileSource`]: ../source/struct.FileSource.html
+    pub fn into_file_source(
+        self,
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+    ) -> Result<FileSource, L10nRegistrySetupError> {
+        let mut index = self.index();
+        // The full paths of a source whose `pre_path` starts with `/` do so
+        // as well, unlike the names of the archive entries.
+        if pre_path.starts_with('/') {
+            for path in &mut index {
+                path.insert(0, '/');
+            }
+        }
+        FileSource::try_new_with_index(name, metasource, locales, pre_path, options, self, index)
+    }
+}
+
+/// The name of the archive entry of `resource_id`.
+fn entry_name(resource_id: &ResourceId) -> &str {
+    let value = &resource_id.value;
+    value.strip_prefix('/').unwrap_or(value)
+}
+
+fn zip_to_io_error(err: ZipError) -> io::Error {
+    match err {
+        ZipError::Io(err) => err,
+        ZipError::FileNotFound => io::Error::new(io::ErrorKind::NotFound, err),
+        ZipError::InvalidArchive(_) | ZipError::UnsupportedArchive(_) => {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<R: Read + Seek> FileFetcher for ArchiveFileFetcher<R> {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        String::from_utf8(self.fetch_bytes_sync(resource_id)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.fetch_sync(resource_id)
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut file = archive
+            .by_name(entry_name(resource_id))
+            .map_err(zip_to_io_error)?;
+        if file.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is a directory", resource_id.value),
+            ));
+        }
+        // The size in the header is not trusted for preallocation, since a
+        // malformed archive could claim any size.
+        let mut source = vec![];
+        file.read_to_end(&mut source)?;
+        Ok(source)
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_bytes_sync(resource_id)
+    }
+
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        let mut archive = self.archive.borrow_mut();
+        let file = archive.by_name(entry_name(resource_id)).ok()?;
+        Some(format!("{}:{:08x}", file.size(), file.crc32()))
+    }
+}
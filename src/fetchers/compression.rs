@@ -0,0 +1,341 @@
+#[cfg(feature = "sync")]
+use crate::concurrent;
+use crate::source::{FileFetcher, ResourceId};
+use async_trait::async_trait;
+use futures::Future;
+use std::{fmt, io, io::Read};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// A compression format understood by [`DecompressingFileFetcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Brotli,
+}
+
+impl Compression {
+    /// The formats in the order their variants are looked up.
+    const ALL: [Compression; 2] = [Compression::Gzip, Compression::Brotli];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Brotli => ".br",
+        }
+    }
+
+    /// Detect the format of the resource stored at `path` from its
+    /// extension, or, for gzip which has a header, from its first bytes.
+    pub fn detect(path: &str, bytes: &[u8]) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|compression| path.ends_with(compression.extension()))
+            .or_else(|| {
+                if bytes.starts_with(GZIP_MAGIC) {
+                    Some(Compression::Gzip)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Decompress `bytes`, failing with [`io::ErrorKind::InvalidData`] if
+    /// the output would be longer than `max_size`.
+    pub fn decompress(self, bytes: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+        let mut output = vec![];
+        // Read one byte past the limit to tell a full output from a cut one.
+        let limit = max_size as u64 + 1;
+        match self {
+            Compression::Gzip => flate2::read::GzDecoder::new(bytes)
+                .take(limit)
+                .read_to_end(&mut output)?,
+            Compression::Brotli => brotli::Decompressor::new(bytes, 4096)
+                .take(limit)
+                .read_to_end(&mut output)?,
+        };
+        if output.len() > max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Decompressed data exceeds {} bytes", max_size),
+            ));
+        }
+        Ok(output)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Brotli => write!(f, "brotli"),
+        }
+    }
+}
+
+/// A [`FileFetcher`] wrapping another one, and transparently decompressing
+/// the resources it stores compressed.
+///
+/// A resource which the inner fetcher doesn't have is looked up again with
+/// the extension of each [`Compression`] appended, e.g. `menu.ftl.gz` and
+/// then `menu.ftl.br` for `menu.ftl`. Resources are decompressed according
+/// to the extension they were found under, or if they start with the gzip
+/// header.
+///
+/// Corrupted data, and data which decompresses to more than the
+/// [`max_size`](#method.max_size), is reported as
+/// [`io::ErrorKind::InvalidData`], so that the [`FileSource`] reports it as a
+/// fetch error rather than a missing file.
+///
+/// [`FileFetcher`]: ../source/trait.FileFetcher.html
+/// [`FileSource`]: ../source/struct.FileSource.html
+#[derive(Debug, Clone)]
+pub struct DecompressingFileFetcher<F> {
+    inner: F,
+    max_size: usize,
+}
+
+impl<F> DecompressingFileFetcher<F> {
+    /// The default limit on the size of a decompressed resource.
+    pub const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner,
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Limit the size of decompressed resources to `max_size` bytes.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    /// The paths to try for `resource_id`, in order. A path which already
+    /// has the extension of a compression format has no variants.
+    fn candidates(resource_id: &ResourceId) -> Vec<ResourceId> {
+        let mut candidates = vec![resource_id.clone()];
+        let compressed = Compression::ALL
+            .iter()
+            .any(|compression| resource_id.value.ends_with(compression.extension()));
+        if !compressed {
+            candidates.extend(Compression::ALL.iter().map(|compression| {
+                ResourceId::new(
+                    format!("{}{}", resource_id.value, compression.extension()),
+                    resource_id.resource_type,
+                )
+            }));
+        }
+        candidates
+    }
+
+    /// Fetch the first candidate of `resource_id` found with `fetch`, and
+    /// decompress it. If there is none, the error for the first candidate is
+    /// reported.
+    fn fetch_candidates_sync(
+        &self,
+        resource_id: &ResourceId,
+        fetch: impl Fn(&ResourceId) -> io::Result<Vec<u8>>,
+    ) -> io::Result<Vec<u8>> {
+        let mut not_found = None;
+        for candidate in Self::candidates(resource_id) {
+            match fetch(&candidate) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    not_found.get_or_insert(err);
+                }
+                result => return decode(&candidate, result?, self.max_size),
+            }
+        }
+        Err(not_found.expect("There is at least one candidate"))
+    }
+
+    /// The async version of [`fetch_candidates_sync`](#method.fetch_candidates_sync).
+    async fn fetch_candidates<Fut>(
+        &self,
+        resource_id: &ResourceId,
+        fetch: impl Fn(ResourceId) -> Fut,
+    ) -> io::Result<Vec<u8>>
+    where
+        Fut: Future<Output = io::Result<Vec<u8>>>,
+    {
+        let mut not_found = None;
+        for candidate in Self::candidates(resource_id) {
+            match fetch(candidate.clone()).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    not_found.get_or_insert(err);
+                }
+                result => return decode(&candidate, result?, self.max_size),
+            }
+        }
+        Err(not_found.expect("There is at least one candidate"))
+    }
+
+    /// Fetch the candidates of `resource_ids` with `fetch_many`, requesting
+    /// the next candidate of the resources not found yet in each round.
+    async fn fetch_many_candidates<Fut>(
+        &self,
+        resource_ids: &[ResourceId],
+        fetch_many: impl Fn(Vec<ResourceId>) -> Fut,
+    ) -> Vec<io::Result<Vec<u8>>>
+    where
+        Fut: Future<Output = Vec<io::Result<Vec<u8>>>>,
+    {
+        let candidates: Vec<Vec<ResourceId>> = resource_ids.iter().map(Self::candidates).collect();
+        let mut results: Vec<Option<io::Result<Vec<u8>>>> =
+            resource_ids.iter().map(|_| None).collect();
+        let mut not_found: Vec<Option<io::Error>> = resource_ids.iter().map(|_| None).collect();
+        let mut remaining: Vec<usize> = (0..resource_ids.len()).collect();
+        let mut round = 0;
+        while !remaining.is_empty() {
+            let batch: Vec<ResourceId> = remaining
+                .iter()
+                .map(|&idx| candidates[idx][round].clone())
+                .collect();
+            let fetched = fetch_many(batch).await;
+            let mut next = vec![];
+            for (idx, result) in remaining.into_iter().zip(fetched) {
+                results[idx] =
+                    match result {
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                            // Report the error for the first candidate.
+                            let err = not_found[idx].take().unwrap_or(err);
+                            if round + 1 < candidates[idx].len() {
+                                not_found[idx] = Some(err);
+                                next.push(idx);
+                                continue;
+                            }
+                            Some(Err(err))
+                        }
+                        result => Some(result.and_then(|bytes| {
+                            decode(&candidates[idx][round], bytes, self.max_size)
+                        })),
+                    };
+            }
+            remaining = next;
+            round += 1;
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("Every resource has a result"))
+            .collect()
+    }
+}
+
+fn decode(resource_id: &ResourceId, bytes: Vec<u8>, max_size: usize) -> io::Result<Vec<u8>> {
+    match Compression::detect(&resource_id.value, &bytes) {
+        Some(compression) => compression.decompress(&bytes, max_size).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid {} data in {}: {}",
+                    compression, resource_id.value, err
+                ),
+            )
+        }),
+        None => Ok(bytes),
+    }
+}
+
+fn into_string(bytes: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[async_trait(?Send)]
+impl<F: FileFetcher> FileFetcher for DecompressingFileFetcher<F> {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        into_string(self.fetch_bytes_sync(resource_id)?)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        into_string(self.fetch_bytes(resource_id).await?)
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_candidates_sync(resource_id, |candidate| {
+            self.inner.fetch_bytes_sync(candidate)
+        })
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_candidates(resource_id, |candidate| async move {
+            self.inner.fetch_bytes(&candidate).await
+        })
+        .await
+    }
+
+    /// Forwards to the inner fetcher's `fetch_many`, requesting the next
+    /// candidate of the resources not found yet in each round.
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        self.fetch_many_candidates(resource_ids, |batch| async move {
+            self.inner.fetch_many(&batch).await
+        })
+        .await
+    }
+
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        Self::candidates(resource_id)
+            .iter()
+            .find_map(|candidate| self.inner.stamp(candidate))
+    }
+
+    fn origin(&self, resource_id: &ResourceId) -> Option<String> {
+        Self::candidates(resource_id)
+            .iter()
+            .find_map(|candidate| self.inner.origin(candidate))
+    }
+}
+
+/// Mirrors the [`FileFetcher`] implementation for an inner fetcher which
+/// can be shared between threads.
+#[cfg(feature = "sync")]
+#[async_trait]
+impl<F: concurrent::FileFetcher> concurrent::FileFetcher for DecompressingFileFetcher<F> {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        into_string(concurrent::FileFetcher::fetch_bytes_sync(
+            self,
+            resource_id,
+        )?)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        into_string(concurrent::FileFetcher::fetch_bytes(self, resource_id).await?)
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_candidates_sync(resource_id, |candidate| {
+            self.inner.fetch_bytes_sync(candidate)
+        })
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_candidates(resource_id, |candidate| async move {
+            self.inner.fetch_bytes(&candidate).await
+        })
+        .await
+    }
+
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        self.fetch_many_candidates(resource_ids, |batch| async move {
+            self.inner.fetch_many(&batch).await
+        })
+        .await
+    }
+
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        Self::candidates(resource_id)
+            .iter()
+            .find_map(|candidate| self.inner.stamp(candidate))
+    }
+
+    fn origin(&self, resource_id: &ResourceId) -> Option<String> {
+        Self::candidates(resource_id)
+            .iter()
+            .find_map(|candidate| self.inner.origin(candidate))
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(feature = "sync")]
+use crate::concurrent;
+use crate::source::{FileFetcher, ResourceId};
+use async_trait::async_trait;
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// A [`FileFetcher`] reading resources from a directory on disk.
+///
+/// The `value` of each [`ResourceId`] is interpreted as a path relative to
+/// the `root` directory, so a `FileSource` with a `pre_path` of `"{locale}/"`
+/// backed by this fetcher will read `<root>/en-US/browser/menu.ftl` for a
+/// `browser/menu.ftl` resource.
+///
+/// The synchronous fetch uses `std::fs`. When the `tokio-io` feature is
+/// enabled, the asynchronous fetch uses `tokio::fs`, otherwise it falls back
+/// to a blocking read.
+///
+/// A missing file is reported as [`io::ErrorKind::NotFound`]; all other
+/// failures (permissions, invalid UTF-8, reading a directory, ...) keep their
+/// original kind so that callers can tell them apart.
+///
+/// With the `sync` feature, it also implements the [`concurrent::FileFetcher`]
+/// trait, so that it can back the sources of a registry shared between
+/// threads.
+///
+/// [`FileFetcher`]: ../source/trait.FileFetcher.html
+/// [`concurrent::FileFetcher`]: ../concurrent/trait.FileFetcher.html
+#[derive(Debug, Clone)]
+pub struct DirectoryFileFetcher {
+    root: PathBuf,
+}
+
+impl DirectoryFileFetcher {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a resource id to a path inside of the root directory.
+    ///
+    /// A single leading `/`, as in a `pre_path` of
+    /// `"/browser/data/locale/{locale}/"`, is relative to the root. Other
+    /// absolute paths and paths escaping the root via `..` are rejected
+    /// with [`io::ErrorKind::InvalidInput`].
+    pub fn get_path(&self, resource_id: &ResourceId) -> io::Result<PathBuf> {
+        let value = &resource_id.value;
+        let relative = Path::new(value.strip_prefix('/').unwrap_or(value));
+        let is_contained = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !is_contained {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Resource path {} points outside of {}",
+                    resource_id.value,
+                    self.root.display()
+                ),
+            ));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn read_to_string_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        std::fs::read_to_string(self.get_path(resource_id)?)
+    }
+
+    #[cfg(feature = "tokio-io")]
+    async fn read_to_string(&self, resource_id: &ResourceId) -> io::Result<String> {
+        tokio::fs::read_to_string(self.get_path(resource_id)?).await
+    }
+
+    #[cfg(not(feature = "tokio-io"))]
+    async fn read_to_string(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.read_to_string_sync(resource_id)
+    }
+
+    fn read_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        std::fs::read(self.get_path(resource_id)?)
+    }
+
+    #[cfg(feature = "tokio-io")]
+    async fn read(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        tokio::fs::read(self.get_path(resource_id)?).await
+    }
+
+    #[cfg(not(feature = "tokio-io"))]
+    async fn read(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.read_sync(resource_id)
+    }
+
+    /// The size and modification time of the file of `resource_id`.
+    fn file_stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        let metadata = std::fs::metadata(self.get_path(resource_id).ok()?).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(format!("{}:{}", metadata.len(), modified.as_nanos()))
+    }
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for DirectoryFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.read_to_string_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.read_to_string(resource_id).await
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.read_sync(resource_id)
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.read(resource_id).await
+    }
+
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        self.file_stamp(resource_id)
+    }
+}
+
+#[cfg(feature = "sync")]
+#[async_trait]
+impl concurrent::FileFetcher for DirectoryFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.read_to_string_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.read_to_string(resource_id).await
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.read_sync(resource_id)
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.read(resource_id).await
+    }
+
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        self.file_stamp(resource_id)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::source::{FileFetcher, ResourceId};
+use async_trait::async_trait;
+use rustc_hash::FxHashMap;
+use std::{cell::RefCell, io, iter::FromIterator, rc::Rc};
+
+/// A [`FileFetcher`] serving resources from an in-memory map of paths to
+/// FTL sources.
+///
+/// The fetcher is a cheap, cloneable handle: all clones share the same map,
+/// so an application can keep one clone around and register generated or
+/// downloaded strings after the `FileSource` has been created.
+///
+/// Note that `FileSource` caches every resource it has loaded, so changing
+/// an entry that has already been fetched does not affect the cached copy.
+///
+/// [`FileFetcher`]: ../source/trait.FileFetcher.html
+#[derive(Clone, Default)]
+pub struct MemoryFileFetcher {
+    files: Rc<RefCell<FxHashMap<String, String>>>,
+}
+
+impl MemoryFileFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the source stored under `path`, returning the previous
+    /// one, if any.
+    pub fn insert<P, S>(&self, path: P, source: S) -> Option<String>
+    where
+        P: ToString,
+        S: ToString,
+    {
+        self.files
+            .borrow_mut()
+            .insert(path.to_string(), source.to_string())
+    }
+
+    /// Remove the source stored under `path`, returning it, if any.
+    pub fn remove(&self, path: &str) -> Option<String> {
+        self.files.borrow_mut().remove(path)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.files.borrow().contains_key(path)
+    }
+
+    pub fn clear(&self) {
+        self.files.borrow_mut().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.borrow().is_empty()
+    }
+
+    /// Returns the paths of all stored sources, in the format expected by
+    /// [`FileSource::new_with_index`].
+    ///
+    /// [`FileSource::new_with_index`]: ../source/struct.FileSource.html#method.new_with_index
+    pub fn index(&self) -> Vec<String> {
+        self.files.borrow().keys().cloned().collect()
+    }
+}
+
+impl<P, S> FromIterator<(P, S)> for MemoryFileFetcher
+where
+    P: ToString,
+    S: ToString,
+{
+    fn from_iter<I: IntoIterator<Item = (P, S)>>(iter: I) -> Self {
+        let fetcher = Self::new();
+        for (path, source) in iter {
+            fetcher.insert(path, source);
+        }
+        fetcher
+    }
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for MemoryFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.files
+            .borrow()
+            .get(&resource_id.value)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, resource_id.value.clone()))
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.fetch_sync(resource_id)
+    }
+}
@@ -0,0 +1,18 @@
+//! Ready-made [`FileFetcher`](../source/trait.FileFetcher.html) implementations.
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "compression")]
+mod compression;
+mod directory;
+mod memory;
+mod overlay;
+mod watcher;
+
+#[cfg(feature = "archive")]
+pub use archive::ArchiveFileFetcher;
+#[cfg(feature = "compression")]
+pub use compression::{Compression, DecompressingFileFetcher};
+pub use directory::DirectoryFileFetcher;
+pub use memory::MemoryFileFetcher;
+pub use overlay::OverlayFileFetcher;
+pub use watcher::DirectoryWatcher;
@@ -0,0 +1,192 @@
+use crate::source::{FileFetcher, ResourceId};
+use async_trait::async_trait;
+use rustc_hash::FxHashMap;
+use std::{cell::RefCell, io, rc::Rc};
+
+#[derive(Clone)]
+struct Layer {
+    name: String,
+    fetcher: Rc<dyn FileFetcher>,
+}
+
+/// A [`FileFetcher`] stacking several fetchers, e.g. a directory of user
+/// overrides on top of the packaged archive, for a single `FileSource`.
+///
+/// Each resource is fetched from the first layer which has it, in the order
+/// the layers were added. A layer failing with an error other than
+/// [`io::ErrorKind::NotFound`] hides the layers below it, so that a broken
+/// override is reported rather than silently skipped.
+///
+/// The fetcher remembers which layer served each resource, see
+/// [`served_by`](#method.served_by). All clones share that record, which
+/// also names the layer in the errors a `FileSource` reports for the
+/// resource, see [`FileFetcher::origin`].
+///
+/// [`FileFetcher`]: ../source/trait.FileFetcher.html
+/// [`FileFetcher::origin`]: ../source/trait.FileFetcher.html#method.origin
+#[derive(Clone, Default)]
+pub struct OverlayFileFetcher {
+    layers: Vec<Layer>,
+    /// The layer which answered the last fetch of each path, and whether
+    /// the fetch succeeded.
+    served_by: Rc<RefCell<FxHashMap<String, (usize, bool)>>>,
+}
+
+impl OverlayFileFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer below the existing ones.
+    pub fn layer<S, F>(mut self, name: S, fetcher: F) -> Self
+    where
+        S: ToString,
+        F: FileFetcher + 'static,
+    {
+        self.layers.push(Layer {
+            name: name.to_string(),
+            fetcher: Rc::new(fetcher),
+        });
+        self
+    }
+
+    pub fn layer_names(&self) -> Vec<&str> {
+        self.layers
+            .iter()
+            .map(|layer| layer.name.as_str())
+            .collect()
+    }
+
+    /// Returns the name of the layer which served the last successful fetch
+    /// of `path`, or `None` if it hasn't been fetched or its last fetch
+    /// failed.
+    pub fn served_by(&self, path: &str) -> Option<&str> {
+        match *self.served_by.borrow().get(path)? {
+            (idx, true) => Some(&self.layers[idx].name),
+            (_, false) => None,
+        }
+    }
+
+    fn record<T>(&self, resource_id: &ResourceId, idx: usize, result: &io::Result<T>) {
+        self.served_by
+            .borrow_mut()
+            .insert(resource_id.value.clone(), (idx, result.is_ok()));
+    }
+
+    fn not_found(&self, resource_id: &ResourceId) -> io::Error {
+        self.served_by.borrow_mut().remove(&resource_id.value);
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} not found in any of the layers {:?}",
+                resource_id.value,
+                self.layer_names()
+            ),
+        )
+    }
+
+    fn fetch_first<T>(
+        &self,
+        resource_id: &ResourceId,
+        fetch: impl Fn(&dyn FileFetcher) -> io::Result<T>,
+    ) -> io::Result<T> {
+        for (idx, layer) in self.layers.iter().enumerate() {
+            match fetch(layer.fetcher.as_ref()) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                result => {
+                    self.record(resource_id, idx, &result);
+                    return result;
+                }
+            }
+        }
+        Err(self.not_found(resource_id))
+    }
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for OverlayFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.fetch_first(resource_id, |fetcher| fetcher.fetch_sync(resource_id))
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        for (idx, layer) in self.layers.iter().enumerate() {
+            match layer.fetcher.fetch(resource_id).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                result => {
+                    self.record(resource_id, idx, &result);
+                    return result;
+                }
+            }
+        }
+        Err(self.not_found(resource_id))
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_first(resource_id, |fetcher| fetcher.fetch_bytes_sync(resource_id))
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        for (idx, layer) in self.layers.iter().enumerate() {
+            match layer.fetcher.fetch_bytes(resource_id).await {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                result => {
+                    self.record(resource_id, idx, &result);
+                    return result;
+                }
+            }
+        }
+        Err(self.not_found(resource_id))
+    }
+
+    /// Fetches the resources with a `fetch_many` call to each layer in turn,
+    /// requesting the ones which the layers above didn't have.
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        let mut results: Vec<Option<io::Result<Vec<u8>>>> =
+            resource_ids.iter().map(|_| None).collect();
+        let mut remaining: Vec<usize> = (0..resource_ids.len()).collect();
+        for (layer_idx, layer) in self.layers.iter().enumerate() {
+            if remaining.is_empty() {
+                break;
+            }
+            let batch: Vec<ResourceId> = remaining
+                .iter()
+                .map(|&idx| resource_ids[idx].clone())
+                .collect();
+            let fetched = layer.fetcher.fetch_many(&batch).await;
+            let mut next = vec![];
+            for (idx, result) in remaining.into_iter().zip(fetched) {
+                match result {
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => next.push(idx),
+                    result => {
+                        self.record(&resource_ids[idx], layer_idx, &result);
+                        results[idx] = Some(result);
+                    }
+                }
+            }
+            remaining = next;
+        }
+        for idx in remaining {
+            results[idx] = Some(Err(self.not_found(&resource_ids[idx])));
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("Every resource has a result"))
+            .collect()
+    }
+
+    /// The name of the layer which answered the last fetch of `path`,
+    /// whether it succeeded or not.
+    fn origin(&self, resource_id: &ResourceId) -> Option<String> {
+        let (idx, _) = *self.served_by.borrow().get(&resource_id.value)?;
+        Some(self.layers[idx].name.clone())
+    }
+
+    /// The stamp of the first layer providing one, along with its name.
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        self.layers.iter().find_map(|layer| {
+            let stamp = layer.fetcher.stamp(resource_id)?;
+            Some(format!("{}:{}", layer.name, stamp))
+        })
+    }
+}
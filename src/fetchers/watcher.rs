@@ -0,0 +1,190 @@
+use crate::errors::L10nRegistrySetupError;
+use crate::registry::L10nRegistry;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[cfg(feature = "watch")]
+use notify::Watcher;
+#[cfg(feature = "watch")]
+use std::sync::mpsc;
+
+type Snapshot = FxHashMap<PathBuf, (Option<SystemTime>, u64)>;
+
+enum Backend {
+    /// Compare the modification times and sizes of all files on every poll.
+    Polling(Snapshot),
+    /// Collect change events from the platform's file notification API.
+    #[cfg(feature = "watch")]
+    Native {
+        // Kept alive for as long as events should be delivered.
+        _watcher: notify::RecommendedWatcher,
+        events: mpsc::Receiver<notify::Result<notify::Event>>,
+    },
+}
+
+/// Detects changes to the files of a directory read by a
+/// [`DirectoryFileFetcher`](struct.DirectoryFileFetcher.html), and
+/// invalidates them in the `FileSource`s using it.
+///
+/// The watcher does not spawn anything that touches the registry: changes
+/// are picked up when [`update_registry`](#method.update_registry) is called,
+/// e.g. from a timer on the thread owning the registry.
+///
+/// With the `watch` feature, the platform's file notification API is used
+/// when available. Otherwise, and with [`polling`](#method.polling), each
+/// update compares the modification times and sizes of all files in the
+/// directory against the previous update.
+pub struct DirectoryWatcher {
+    root: PathBuf,
+    sources: Vec<String>,
+    backend: Backend,
+}
+
+impl DirectoryWatcher {
+    /// Watch `root` on behalf of the sources named in `sources`.
+    ///
+    /// The root is canonicalized, since file notification APIs may report
+    /// absolute paths with symlinks resolved.
+    pub fn new<P: Into<PathBuf>>(root: P, sources: Vec<String>) -> io::Result<Self> {
+        let root = fs::canonicalize(root.into())?;
+        #[cfg(feature = "watch")]
+        {
+            match Self::native_backend(&root) {
+                Ok(backend) => Ok(Self {
+                    root,
+                    sources,
+                    backend,
+                }),
+                Err(_) => Self::polling(root, sources),
+            }
+        }
+        #[cfg(not(feature = "watch"))]
+        Self::polling(root, sources)
+    }
+
+    /// Watch `root` by scanning it on every update.
+    pub fn polling<P: Into<PathBuf>>(root: P, sources: Vec<String>) -> io::Result<Self> {
+        let root = root.into();
+        let mut snapshot = Snapshot::default();
+        scan_directory(&root, &mut snapshot)?;
+        Ok(Self {
+            root,
+            sources,
+            backend: Backend::Polling(snapshot),
+        })
+    }
+
+    #[cfg(feature = "watch")]
+    fn native_backend(root: &Path) -> notify::Result<Backend> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(root, notify::RecursiveMode::Recursive)?;
+        Ok(Backend::Native {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the paths, relative to the root and separated by `/`, of all
+    /// files added, modified or removed since the previous call.
+    pub fn poll_changes(&mut self) -> io::Result<Vec<String>> {
+        let mut changed = FxHashSet::default();
+        match &mut self.backend {
+            Backend::Polling(snapshot) => {
+                let mut current = Snapshot::default();
+                scan_directory(&self.root, &mut current)?;
+                for (path, stamp) in &current {
+                    if snapshot.get(path) != Some(stamp) {
+                        changed.insert(path.clone());
+                    }
+                }
+                for path in snapshot.keys() {
+                    if !current.contains_key(path) {
+                        changed.insert(path.clone());
+                    }
+                }
+                *snapshot = current;
+            }
+            #[cfg(feature = "watch")]
+            Backend::Native { events, .. } => {
+                for event in events.try_iter() {
+                    let event = event.map_err(io::Error::other)?;
+                    if event.kind.is_access() {
+                        continue;
+                    }
+                    changed.extend(event.paths);
+                }
+            }
+        }
+
+        let mut changes: Vec<String> = changed
+            .iter()
+            .filter_map(|path| relative_path(&self.root, path))
+            .collect();
+        changes.sort();
+        Ok(changes)
+    }
+
+    /// Invalidate the changed files in the watched sources of `registry`,
+    /// notifying its change listeners. Returns the changed paths.
+    pub fn update_registry<P, B>(
+        &mut self,
+        registry: &L10nRegistry<P, B>,
+    ) -> io::Result<Vec<String>> {
+        let changes = self.poll_changes()?;
+        if !changes.is_empty() {
+            for name in &self.sources {
+                let source = match registry.get_source(name) {
+                    Ok(Some(source)) => source,
+                    // The source has been removed from the registry since.
+                    Ok(None) => continue,
+                    Err(err) => return Err(io::Error::other(err)),
+                };
+                // The changes are relative to the root, while the cache is
+                // keyed by the full paths passed to the fetcher.
+                let full_paths: Vec<String> = if source.pre_path().starts_with('/') {
+                    changes
+                        .iter()
+                        .map(|change| format!("/{}", change))
+                        .collect()
+                } else {
+                    changes.clone()
+                };
+                match registry.invalidate_source_paths(name, &full_paths) {
+                    Ok(()) | Err(L10nRegistrySetupError::MissingSource { .. }) => {}
+                    Err(err) => return Err(io::Error::other(err)),
+                }
+            }
+        }
+        Ok(changes)
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let segments: Option<Vec<&str>> = relative.iter().map(|s| s.to_str()).collect();
+    Some(segments?.join("/"))
+}
+
+fn scan_directory(dir: &Path, snapshot: &mut Snapshot) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            scan_directory(&entry.path(), snapshot)?;
+        } else {
+            snapshot.insert(entry.path(), (metadata.modified().ok(), metadata.len()));
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,8 @@
+#[cfg(feature = "sync")]
+pub mod concurrent;
 pub mod env;
 pub mod errors;
+pub mod fetchers;
 pub mod fluent;
 pub mod registry;
 pub mod solver;
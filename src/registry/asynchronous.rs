@@ -46,7 +46,9 @@ where
     }
 }
 
+#[derive(Default)]
 enum State<P, B> {
+    #[default]
     Empty,
     Locale(LanguageIdentifier),
     Solver {
@@ -55,12 +57,6 @@ enum State<P, B> {
     },
 }
 
-impl<P, B> Default for State<P, B> {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
-
 impl<P, B> State<P, B> {
     fn get_locale(&self) -> &LanguageIdentifier {
         match self {
@@ -124,20 +120,41 @@ impl Future for TestResult {
     }
 }
 
-impl<'l, P, B> AsyncTester for GenerateBundles<P, B> {
+impl<P, B> AsyncTester for GenerateBundles<P, B> {
     type Result = TestResult;
 
     fn test_async(&self, query: Vec<(usize, usize)>) -> Self::Result {
         let locale = self.state.get_locale();
         let lock = self.reg.lock();
 
-        let stream = query
-            .iter()
-            .map(|(res_idx, source_idx)| {
-                let resource_id = &self.resource_ids[*res_idx];
-                lock.source_idx(self.current_metasource, *source_idx)
-                    .fetch_file(locale, resource_id)
-            })
+        // Group the cells by source, so that each source can fetch all of its
+        // resources at once.
+        let mut sources: Vec<(usize, Vec<usize>)> = vec![];
+        for (cell_idx, (_, source_idx)) in query.iter().enumerate() {
+            match sources.iter_mut().find(|(idx, _)| idx == source_idx) {
+                Some((_, cells)) => cells.push(cell_idx),
+                None => sources.push((*source_idx, vec![cell_idx])),
+            }
+        }
+
+        let mut statuses: Vec<Option<ResourceStatus>> = query.iter().map(|_| None).collect();
+        for (source_idx, cells) in sources {
+            let resource_ids: Vec<ResourceId> = cells
+                .iter()
+                .map(|&cell_idx| self.resource_ids[query[cell_idx].0].clone())
+                .collect();
+            let source = lock.source_idx(self.current_metasource, source_idx);
+            for (cell_idx, status) in cells
+                .into_iter()
+                .zip(source.fetch_files(locale, &resource_ids))
+            {
+                statuses[cell_idx] = Some(status);
+            }
+        }
+
+        let stream = statuses
+            .into_iter()
+            .map(|status| status.expect("Every cell has a status"))
             .collect::<FuturesOrdered<_>>();
         TestResult(stream.collect::<_>())
     }
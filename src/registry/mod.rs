@@ -1,4 +1,7 @@
 mod asynchronous;
+mod preload;
+#[cfg(feature = "signing")]
+mod signing;
 mod synchronous;
 
 use std::{
@@ -8,7 +11,7 @@ use std::{
 };
 
 use crate::errors::L10nRegistrySetupError;
-use crate::source::{FileSource, ResourceId};
+use crate::source::{FileSource, ResourceId, SourceStats, StartupCache};
 
 use crate::env::ErrorReporter;
 use crate::fluent::FluentBundle;
@@ -17,15 +20,25 @@ use fluent_fallback::generator::BundleGenerator;
 use unic_langid::LanguageIdentifier;
 
 pub use asynchronous::GenerateBundles;
+pub use preload::{PreloadReport, SourcePreloadReport};
+#[cfg(feature = "signing")]
+pub use signing::{PublicKey, VerifiedSource};
 pub use synchronous::GenerateBundlesSync;
 
 pub type FluentResourceSet = Vec<Rc<FluentResource>>;
 
+/// A callback notified with a source and the full paths of the resources
+/// invalidated in it. See [`L10nRegistry::add_change_listener`].
+pub type ChangeListener = Rc<dyn Fn(&FileSource, &[String])>;
+
 #[derive(Default)]
 struct Shared<P, B> {
     sources: RefCell<Vec<Vec<FileSource>>>,
     provider: P,
     bundle_adapter: Option<B>,
+    change_listeners: RefCell<Vec<ChangeListener>>,
+    #[cfg(feature = "signing")]
+    trusted_keys: RefCell<Vec<ed25519_dalek::VerifyingKey>>,
 }
 
 pub struct L10nRegistryLocked<'a, B> {
@@ -69,7 +82,7 @@ impl<'a, B> L10nRegistryLocked<'a, B> {
         metasource: usize,
         langid: &'l LanguageIdentifier,
         resource_id: &'l ResourceId,
-    ) -> impl Iterator<Item = &FileSource> {
+    ) -> impl Iterator<Item = &'l FileSource> {
         self.iter(metasource)
             .filter(move |source| source.has_file(langid, resource_id) != Some(false))
     }
@@ -91,6 +104,9 @@ impl<P, B> L10nRegistry<P, B> {
                 sources: Default::default(),
                 provider,
                 bundle_adapter: None,
+                change_listeners: Default::default(),
+                #[cfg(feature = "signing")]
+                trusted_keys: Default::default(),
             }),
         }
     }
@@ -121,6 +137,9 @@ impl<P, B> L10nRegistry<P, B> {
             .try_borrow_mut()
             .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
 
+        for new_source in &new_sources {
+            new_source.check_timer()?;
+        }
         for new_source in new_sources {
             if let Some(metasource) = sources
                 .iter_mut()
@@ -144,6 +163,9 @@ impl<P, B> L10nRegistry<P, B> {
             .try_borrow_mut()
             .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
 
+        for upd_source in &upd_sources {
+            upd_source.check_timer()?;
+        }
         for upd_source in upd_sources {
             if let Some(metasource) = sources
                 .iter_mut()
@@ -221,6 +243,113 @@ impl<P, B> L10nRegistry<P, B> {
             .find(|source| source.name == name)
             .cloned())
     }
+
+    /// Drop the cached copies of `resource_ids` in each of `locales` from
+    /// every registered source, so that they are fetched again the next time
+    /// bundles are generated.
+    pub fn invalidate_resources(
+        &self,
+        locales: &[LanguageIdentifier],
+        resource_ids: &[ResourceId],
+    ) {
+        // Listeners may look sources up again, so don't hold the borrow.
+        let sources: Vec<FileSource> = self
+            .shared
+            .sources
+            .borrow()
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        for source in &sources {
+            let full_paths: Vec<String> = locales
+                .iter()
+                .flat_map(|locale| {
+                    resource_ids
+                        .iter()
+                        .map(move |resource_id| source.get_path(locale, resource_id))
+                })
+                .collect();
+            for full_path in &full_paths {
+                source.invalidate_path(full_path);
+            }
+            self.notify_change_listeners(source, &full_paths);
+        }
+    }
+
+    /// Drop the cached resources stored under `full_paths` in the source
+    /// named `name`, and notify the change listeners about it.
+    pub fn invalidate_source_paths(
+        &self,
+        name: &str,
+        full_paths: &[String],
+    ) -> Result<(), L10nRegistrySetupError> {
+        let source =
+            self.get_source(name)?
+                .ok_or_else(|| L10nRegistrySetupError::MissingSource {
+                    name: name.to_string(),
+                })?;
+        for full_path in full_paths {
+            source.invalidate_path(full_path);
+        }
+        self.notify_change_listeners(&source, full_paths);
+        Ok(())
+    }
+
+    /// Register a callback invoked whenever resources are invalidated through
+    /// [`invalidate_resources`](#method.invalidate_resources) or
+    /// [`invalidate_source_paths`](#method.invalidate_source_paths), e.g. to
+    /// let a `Localization` regenerate its bundles.
+    pub fn add_change_listener<F>(&self, listener: F)
+    where
+        F: Fn(&FileSource, &[String]) + 'static,
+    {
+        self.shared
+            .change_listeners
+            .borrow_mut()
+            .push(Rc::new(listener));
+    }
+
+    fn notify_change_listeners(&self, source: &FileSource, full_paths: &[String]) {
+        if full_paths.is_empty() {
+            return;
+        }
+        // Cloned out, so that listeners can register further listeners.
+        let listeners = self.shared.change_listeners.borrow().clone();
+        for listener in listeners {
+            listener(source, full_paths);
+        }
+    }
+
+    /// Add the resources loaded by every registered source to `cache`, to be
+    /// written out at the end of the session.
+    pub fn save_startup_cache(&self, cache: &mut StartupCache) {
+        for source in self.shared.sources.borrow().iter().flatten() {
+            source.save_startup_cache(cache);
+        }
+    }
+
+    /// Seed the caches of the registered sources with the still valid
+    /// resources of `cache`, see [`FileSource::seed_startup_cache`]. Returns
+    /// the number of resources added.
+    ///
+    /// [`FileSource::seed_startup_cache`]: ../source/struct.FileSource.html#method.seed_startup_cache
+    pub fn seed_startup_cache(&self, cache: &StartupCache) -> usize {
+        let sources = self.shared.sources.borrow();
+        sources
+            .iter()
+            .flatten()
+            .map(|source| source.seed_startup_cache(cache))
+            .sum()
+    }
+
+    /// Returns the sum of the [`stats`](../source/struct.FileSource.html#method.stats)
+    /// of every registered source.
+    pub fn stats(&self) -> SourceStats {
+        let sources = self.shared.sources.borrow();
+        sources.iter().flatten().map(FileSource::stats).sum()
+    }
+
     pub fn get_available_locales(&self) -> Result<Vec<LanguageIdentifier>, L10nRegistrySetupError> {
         let sources = self
             .shared
@@ -232,6 +361,11 @@ impl<P, B> L10nRegistry<P, B> {
             for locale in source.locales() {
                 result.insert(locale);
             }
+            for alias in source.options().aliases.keys() {
+                if source.resolve_locale(alias).is_some() {
+                    result.insert(alias);
+                }
+            }
         }
         Ok(result.into_iter().map(|l| l.to_owned()).collect())
     }
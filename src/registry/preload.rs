@@ -0,0 +1,139 @@
+use super::L10nRegistry;
+use crate::source::{FileSource, ResourceId, ResourceOption};
+use futures::future::join_all;
+use std::io;
+use unic_langid::LanguageIdentifier;
+
+/// What [`L10nRegistry::preload_sync`] and [`L10nRegistry::preload`] found,
+/// for each registered source.
+#[derive(Debug, Clone, Default)]
+pub struct PreloadReport {
+    pub sources: Vec<SourcePreloadReport>,
+}
+
+impl PreloadReport {
+    pub fn get_source(&self, name: &str) -> Option<&SourcePreloadReport> {
+        self.sources.iter().find(|source| source.name == name)
+    }
+}
+
+/// The resources a source was asked to preload, sorted by outcome. Locales
+/// the source doesn't provide are left out.
+#[derive(Debug, Clone, Default)]
+pub struct SourcePreloadReport {
+    pub name: String,
+    /// Resources which are now cached and parsed.
+    pub loaded: Vec<(LanguageIdentifier, ResourceId)>,
+    /// Resources which the source doesn't have.
+    pub missing: Vec<(LanguageIdentifier, ResourceId)>,
+    /// Resources whose fetch failed with an error other than `NotFound`.
+    pub failed: Vec<(LanguageIdentifier, ResourceId, io::ErrorKind)>,
+}
+
+impl SourcePreloadReport {
+    fn new(source: &FileSource) -> Self {
+        Self {
+            name: source.name.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn record(
+        &mut self,
+        locale: &LanguageIdentifier,
+        resource_id: &ResourceId,
+        option: ResourceOption,
+    ) {
+        let entry = (locale.clone(), resource_id.clone());
+        match option {
+            ResourceOption::Some(_) => self.loaded.push(entry),
+            ResourceOption::FetchFailed { kind, .. } => self.failed.push((entry.0, entry.1, kind)),
+            // Neither a sync load which overloads pending async loads, nor an
+            // awaited async load, leaves a resource pending.
+            ResourceOption::MissingOptional
+            | ResourceOption::MissingRequired
+            | ResourceOption::Pending(_) => self.missing.push(entry),
+        }
+    }
+}
+
+impl<P, B> L10nRegistry<P, B> {
+    /// The locales of `locales` which `source` provides, directly or through
+    /// an alias.
+    fn preload_locales<'l>(
+        source: &FileSource,
+        locales: &'l [LanguageIdentifier],
+    ) -> Vec<&'l LanguageIdentifier> {
+        locales
+            .iter()
+            .filter(|locale| source.resolve_locale(locale).is_some())
+            .collect()
+    }
+
+    /// Fetch and parse `resource_ids` in each of `locales` from every
+    /// registered source, so that generating bundles for them later doesn't
+    /// have to wait for any I/O. This version is blocking.
+    pub fn preload_sync(
+        &self,
+        locales: &[LanguageIdentifier],
+        resource_ids: &[ResourceId],
+    ) -> PreloadReport {
+        let sources = self.shared.sources.borrow();
+        let sources = sources
+            .iter()
+            .flatten()
+            .map(|source| {
+                let mut report = SourcePreloadReport::new(source);
+                for locale in Self::preload_locales(source, locales) {
+                    for resource_id in resource_ids {
+                        let option = source.fetch_file_sync(locale, resource_id, true);
+                        report.record(locale, resource_id, option);
+                    }
+                }
+                report
+            })
+            .collect();
+        PreloadReport { sources }
+    }
+
+    /// Fetch and parse `resource_ids` in each of `locales` from every
+    /// registered source, so that generating bundles for them later doesn't
+    /// have to wait for any I/O.
+    ///
+    /// The resources of a source and locale are fetched as one batch, and
+    /// all the batches are awaited concurrently.
+    pub async fn preload(
+        &self,
+        locales: &[LanguageIdentifier],
+        resource_ids: &[ResourceId],
+    ) -> PreloadReport {
+        // Don't hold the borrow of the sources across the await point.
+        let sources: Vec<FileSource> = self
+            .shared
+            .sources
+            .borrow()
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+
+        let reports = sources.iter().map(|source| async move {
+            let locales = Self::preload_locales(source, locales);
+            let batches = locales
+                .iter()
+                .map(|locale| join_all(source.fetch_files(locale, resource_ids)));
+            let options = join_all(batches).await;
+
+            let mut report = SourcePreloadReport::new(source);
+            for (locale, options) in locales.into_iter().zip(options) {
+                for (resource_id, option) in resource_ids.iter().zip(options) {
+                    report.record(locale, resource_id, option);
+                }
+            }
+            report
+        });
+        PreloadReport {
+            sources: join_all(reports).await,
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use super::L10nRegistry;
+use crate::errors::L10nRegistrySetupError;
+use crate::source::{ChecksumManifest, FileFetcher, FileSourceBuilder};
+use ed25519_dalek::{Signature, VerifyingKey};
+use unic_langid::LanguageIdentifier;
+
+pub type PublicKey = [u8; 32];
+
+/// The description of a langpack `FileSource`, read from a manifest whose
+/// signature has been verified by [`L10nRegistry::verify_manifest`].
+///
+/// The manifest is UTF-8 text made of `key: value` headers, a blank line,
+/// and the expected hashes of the langpack's files in the format read by
+/// [`ChecksumManifest::read`]:
+///
+/// ```text
+/// name: langpack-pl
+/// metasource: langpack
+/// locales: pl szl
+/// pre_path: langpack/{locale}/
+///
+/// 1f0c…  langpack/pl/browser/menu.ftl
+/// ```
+///
+/// `metasource` is optional, and `locales` is separated by spaces. Lines may
+/// end with either `\n` or `\r\n`.
+///
+/// [`ChecksumManifest::read`]: ../source/struct.ChecksumManifest.html#method.read
+#[derive(Debug, PartialEq)]
+pub struct VerifiedSource {
+    name: String,
+    metasource: Option<String>,
+    locales: Vec<LanguageIdentifier>,
+    pre_path: String,
+    checksums: ChecksumManifest,
+}
+
+impl VerifiedSource {
+    fn parse(manifest: &[u8]) -> Result<Self, L10nRegistrySetupError> {
+        let invalid = |reason: &str| L10nRegistrySetupError::InvalidManifest {
+            reason: reason.to_string(),
+        };
+        let manifest =
+            std::str::from_utf8(manifest).map_err(|_| invalid("The manifest is not UTF-8"))?;
+        // The headers end at the first blank line, with either line ending.
+        let mut headers_end = manifest.len();
+        let mut checksums_start = manifest.len();
+        let mut offset = 0;
+        for line in manifest.split_inclusive('\n') {
+            if line.trim_end_matches(&['\r', '\n'][..]).is_empty() {
+                headers_end = offset;
+                checksums_start = offset + line.len();
+                break;
+            }
+            offset += line.len();
+        }
+        let headers = &manifest[..headers_end];
+        let checksums = &manifest[checksums_start..];
+
+        let mut name = None;
+        let mut metasource = None;
+        let mut locales = None;
+        let mut pre_path = None;
+        for line in headers.lines() {
+            let (key, value) = match line.find(':') {
+                Some(idx) => (line[..idx].trim(), line[idx + 1..].trim()),
+                None => return Err(invalid(&format!("Invalid header: {}", line))),
+            };
+            match key {
+                "name" => name = Some(value.to_string()),
+                "metasource" => metasource = Some(value.to_string()),
+                "pre_path" => pre_path = Some(value.to_string()),
+                "locales" => {
+                    let parsed: Result<Vec<LanguageIdentifier>, _> =
+                        value.split_whitespace().map(str::parse).collect();
+                    locales =
+                        Some(parsed.map_err(|_| invalid(&format!("Invalid locales: {}", value)))?);
+                }
+                _ => return Err(invalid(&format!("Unknown header: {}", key))),
+            }
+        }
+
+        let checksums = ChecksumManifest::read(checksums.as_bytes())
+            .map_err(|err| invalid(&err.to_string()))?;
+        Ok(Self {
+            name: name.ok_or_else(|| invalid("Missing header: name"))?,
+            metasource,
+            locales: locales.ok_or_else(|| invalid("Missing header: locales"))?,
+            pre_path: pre_path.ok_or_else(|| invalid("Missing header: pre_path"))?,
+            checksums,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn metasource(&self) -> Option<&str> {
+        self.metasource.as_deref()
+    }
+
+    pub fn locales(&self) -> &[LanguageIdentifier] {
+        &self.locales
+    }
+
+    pub fn pre_path(&self) -> &str {
+        &self.pre_path
+    }
+
+    pub fn checksums(&self) -> &ChecksumManifest {
+        &self.checksums
+    }
+
+    /// Returns a builder for the described source, whose resources are
+    /// checked against the manifest's hashes. The hashes replace the
+    /// `checksums` of any options set on the builder.
+    pub fn into_builder(self) -> FileSourceBuilder {
+        let builder = FileSourceBuilder::new(self.name, self.locales, self.pre_path)
+            .require_checksums(self.checksums);
+        match self.metasource {
+            Some(metasource) => builder.metasource(metasource),
+            None => builder,
+        }
+    }
+}
+
+impl<P, B> L10nRegistry<P, B> {
+    /// Trust manifests signed with the ed25519 private key of `key`.
+    ///
+    /// Fails with [`L10nRegistrySetupError::InvalidKey`] if `key` isn't a
+    /// valid ed25519 public key.
+    ///
+    /// [`L10nRegistrySetupError::InvalidKey`]: ../errors/enum.L10nRegistrySetupError.html#variant.InvalidKey
+    pub fn add_trusted_key(&self, key: PublicKey) -> Result<(), L10nRegistrySetupError> {
+        let key = VerifyingKey::from_bytes(&key).map_err(|_| L10nRegistrySetupError::InvalidKey)?;
+        let mut keys = self
+            .shared
+            .trusted_keys
+            .try_borrow_mut()
+            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        Ok(())
+    }
+
+    /// Check that `signature` is an ed25519 signature of `manifest` by one
+    /// of the trusted keys, and return the source it describes.
+    ///
+    /// Fails with [`L10nRegistrySetupError::InvalidSignature`] if no trusted
+    /// key produced the signature, and with
+    /// [`L10nRegistrySetupError::InvalidManifest`] if the signed manifest
+    /// can't be read.
+    ///
+    /// [`L10nRegistrySetupError::InvalidSignature`]: ../errors/enum.L10nRegistrySetupError.html#variant.InvalidSignature
+    /// [`L10nRegistrySetupError::InvalidManifest`]: ../errors/enum.L10nRegistrySetupError.html#variant.InvalidManifest
+    pub fn verify_manifest(
+        &self,
+        manifest: &[u8],
+        signature: &[u8],
+    ) -> Result<VerifiedSource, L10nRegistrySetupError> {
+        let signature = Signature::from_slice(signature)
+            .map_err(|_| L10nRegistrySetupError::InvalidSignature)?;
+        let keys = self
+            .shared
+            .trusted_keys
+            .try_borrow()
+            .map_err(|_| L10nRegistrySetupError::RegistryLocked)?;
+        let trusted = keys
+            .iter()
+            .any(|key| key.verify_strict(manifest, &signature).is_ok());
+        if !trusted {
+            return Err(L10nRegistrySetupError::InvalidSignature);
+        }
+        VerifiedSource::parse(manifest)
+    }
+
+    /// Verify `manifest` as with [`verify_manifest`](#method.verify_manifest),
+    /// and register the source it describes with the default options.
+    pub fn register_signed_source(
+        &self,
+        manifest: &[u8],
+        signature: &[u8],
+        fetcher: impl FileFetcher + 'static,
+    ) -> Result<(), L10nRegistrySetupError> {
+        let source = self
+            .verify_manifest(manifest, signature)?
+            .into_builder()
+            .build(fetcher)?;
+        self.register_sources(vec![source])
+    }
+}
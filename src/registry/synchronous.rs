@@ -33,13 +33,20 @@ impl<'a, B> L10nRegistryLocked<'a, B> {
             if let ResourceOption::Some(res) =
                 source.fetch_file_sync(&locale, resource_id, /* overload */ true)
             {
-                if source.options.allow_override {
+                // Record the locales serving `locale` through an alias.
+                if let Some(resolved) = source.resolve_locale(&locale) {
+                    if !bundle.locales.contains(resolved) {
+                        bundle.locales.push(resolved.clone());
+                    }
+                }
+                if source.options().allow_override {
                     bundle.add_resource_overriding(res);
                 } else if let Err(err) = bundle.add_resource(res) {
                     errors.extend(err.into_iter().map(|error| L10nRegistryError::FluentError {
                         resource_id: resource_id.clone(),
                         loc: None,
                         error,
+                        origin: None,
                     }));
                 }
             } else if resource_id.is_required() {
@@ -78,7 +85,9 @@ where
     }
 }
 
+#[derive(Default)]
 enum State {
+    #[default]
     Empty,
     Locale(LanguageIdentifier),
     Solver {
@@ -87,12 +96,6 @@ enum State {
     },
 }
 
-impl Default for State {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
-
 impl State {
     fn get_locale(&self) -> &LanguageIdentifier {
         match self {
@@ -228,7 +231,7 @@ where
                         let bundle = self.reg.lock().bundle_from_order(
                             self.current_metasource,
                             locale.clone(),
-                            &order,
+                            order,
                             &self.resource_ids,
                             &self.reg.shared.provider,
                         );
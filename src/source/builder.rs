@@ -0,0 +1,115 @@
+use super::{FileFetcher, FileSource, FileSourceOptions};
+use crate::env::{ErrorReporter, Timer};
+use crate::errors::L10nRegistrySetupError;
+use unic_langid::LanguageIdentifier;
+
+#[cfg(feature = "signing")]
+use super::ChecksumManifest;
+#[cfg(feature = "signing")]
+use std::sync::Arc;
+
+/// A builder for [`FileSource`](struct.FileSource.html), for callers who
+/// only need to set some of its optional parts.
+pub struct FileSourceBuilder {
+    name: String,
+    metasource: Option<String>,
+    locales: Vec<LanguageIdentifier>,
+    pre_path: String,
+    options: FileSourceOptions,
+    index: Option<Vec<String>>,
+    error_reporter: Option<Box<dyn ErrorReporter>>,
+    timer: Option<Box<dyn Timer>>,
+    /// Checksums which replace those of the `options`, so that they can't
+    /// be dropped by setting the options again.
+    #[cfg(feature = "signing")]
+    required_checksums: Option<Arc<ChecksumManifest>>,
+}
+
+impl FileSourceBuilder {
+    pub fn new<N, P>(name: N, locales: Vec<LanguageIdentifier>, pre_path: P) -> Self
+    where
+        N: ToString,
+        P: ToString,
+    {
+        Self {
+            name: name.to_string(),
+            metasource: None,
+            locales,
+            pre_path: pre_path.to_string(),
+            options: FileSourceOptions::default(),
+            index: None,
+            error_reporter: None,
+            timer: None,
+            #[cfg(feature = "signing")]
+            required_checksums: None,
+        }
+    }
+
+    pub fn metasource<S: ToString>(mut self, metasource: S) -> Self {
+        self.metasource = Some(metasource.to_string());
+        self
+    }
+
+    pub fn options(mut self, options: FileSourceOptions) -> Self {
+        self.options = options;
+        #[cfg(feature = "signing")]
+        if let Some(checksums) = &self.required_checksums {
+            self.options.checksums = Some(checksums.clone());
+        }
+        self
+    }
+
+    /// Set the list of full paths available in the source.
+    /// See [`FileSource::new_with_index`](struct.FileSource.html#method.new_with_index).
+    pub fn index(mut self, index: Vec<String>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn reporter(mut self, reporter: impl ErrorReporter + 'static) -> Self {
+        self.error_reporter = Some(Box::new(reporter));
+        self
+    }
+
+    #[cfg(feature = "signing")]
+    pub(crate) fn require_checksums(mut self, checksums: ChecksumManifest) -> Self {
+        let checksums = Arc::new(checksums);
+        self.options.checksums = Some(checksums.clone());
+        self.required_checksums = Some(checksums);
+        self
+    }
+
+    /// See [`FileSource::set_timer`](struct.FileSource.html#method.set_timer).
+    pub fn timer(mut self, timer: impl Timer + 'static) -> Self {
+        self.timer = Some(Box::new(timer));
+        self
+    }
+
+    /// Create the `FileSource`, or return an error if its `pre_path` contains
+    /// an unknown placeholder, or if its options set a `fetch_timeout`
+    /// without a timer to enforce it.
+    pub fn build(
+        self,
+        fetcher: impl FileFetcher + 'static,
+    ) -> Result<FileSource, L10nRegistrySetupError> {
+        if self.options.fetch_timeout.is_some() && self.timer.is_none() {
+            return Err(L10nRegistrySetupError::MissingTimer { name: self.name });
+        }
+        let mut source = FileSource::with_optional_index(
+            self.name,
+            self.metasource,
+            self.locales,
+            self.pre_path,
+            self.options,
+            fetcher,
+            self.index,
+        )?;
+        if let Some(reporter) = self.error_reporter {
+            source.set_boxed_reporter(reporter);
+        }
+        if let Some(timer) = self.timer {
+            source.set_boxed_timer(timer);
+        }
+        Ok(source)
+    }
+}
@@ -0,0 +1,481 @@
+use super::{RcResource, ResourceStatus};
+use futures::future::{Shared, WeakShared};
+use futures::Future;
+use rustc_hash::FxHashMap;
+use std::collections::hash_map::Entry;
+use std::pin::Pin;
+use unic_langid::LanguageIdentifier;
+
+/// Limits on the resources a [`FileSource`] keeps in its cache.
+///
+/// Entries which are still loading are never evicted, and missing resources
+/// are only dropped together with their locale, so that repeated probes for
+/// absent files stay cheap.
+///
+/// [`FileSource`]: struct.FileSource.html
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub enum CachePolicy {
+    /// Keep every resource for the lifetime of the source.
+    #[default]
+    Unbounded,
+    /// Keep loaded resources while the total length of their sources stays
+    /// within `max_bytes`, evicting the least recently used ones first.
+    Lru { max_bytes: usize },
+    /// Keep the resources of the `max_locales` most recently used locales,
+    /// evicting all entries of a locale at once when it falls out.
+    Locales { max_locales: usize },
+}
+
+/// The status of a resource, as stored in a [`ResourceCache`]. Implemented
+/// by the `ResourceStatus` of both the local and the thread-safe sources.
+pub(crate) trait CacheStatus: Clone + From<Self::Output> {
+    type Output: Clone;
+    type Future: Future<Output = Self::Output>;
+
+    /// Returns the future of a loading status.
+    fn as_loading(&self) -> Option<&Shared<Self::Future>>;
+
+    fn loading(future: Shared<Self::Future>) -> Self;
+
+    /// Returns the length of the source of a loaded resource.
+    fn loaded_size(&self) -> Option<usize>;
+}
+
+impl CacheStatus for ResourceStatus {
+    type Output = super::ResourceOption;
+    type Future = Pin<Box<dyn Future<Output = Self::Output>>>;
+
+    fn as_loading(&self) -> Option<&Shared<Self::Future>> {
+        match self {
+            Self::Loading(future) => Some(future),
+            _ => None,
+        }
+    }
+
+    fn loading(future: Shared<Self::Future>) -> Self {
+        Self::Loading(future)
+    }
+
+    fn loaded_size(&self) -> Option<usize> {
+        match self {
+            Self::Loaded(res) => Some(res.source().len()),
+            _ => None,
+        }
+    }
+}
+
+/// A status as stored in the cache.
+///
+/// A loading entry only holds a weak reference to its future, so that the
+/// load is dropped together with the last future waiting for it, rather than
+/// staying in the cache forever.
+enum CachedStatus<S: CacheStatus> {
+    Loading(WeakShared<S::Future>),
+    Done(S),
+}
+
+impl<S: CacheStatus> CachedStatus<S> {
+    fn new(status: S) -> Self {
+        match status.as_loading() {
+            Some(future) => match future.downgrade() {
+                Some(future) => Self::Loading(future),
+                None => Self::Done(
+                    future
+                        .peek()
+                        .expect("A completed future has an output")
+                        .clone()
+                        .into(),
+                ),
+            },
+            None => Self::Done(status),
+        }
+    }
+
+    /// Returns the status, or `None` if the entry is loading but nobody
+    /// waits for the load anymore.
+    fn upgrade(&self) -> Option<S> {
+        match self {
+            Self::Loading(future) => future.upgrade().map(S::loading),
+            Self::Done(status) => Some(status.clone()),
+        }
+    }
+
+    fn is_loading(&self) -> bool {
+        matches!(self, Self::Loading(_))
+    }
+
+    fn loaded_size(&self) -> Option<usize> {
+        match self {
+            Self::Done(status) => status.loaded_size(),
+            Self::Loading(_) => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.loaded_size().unwrap_or(0)
+    }
+}
+
+struct CacheEntry<S: CacheStatus> {
+    /// Unique for every entry inserted into the cache, so that a load
+    /// finishing after its entry was invalidated doesn't overwrite a newer one.
+    id: u64,
+    status: CachedStatus<S>,
+    locale: LanguageIdentifier,
+    /// The `FileFetcher::stamp` of the resource, read when it was fetched.
+    stamp: Option<String>,
+    last_used: u64,
+}
+
+/// The cache of a `FileSource`, mapping full paths to their
+/// [`ResourceStatus`](enum.ResourceStatus.html).
+pub(crate) struct ResourceCache<S: CacheStatus = ResourceStatus> {
+    policy: CachePolicy,
+    entries: FxHashMap<String, CacheEntry<S>>,
+    clock: u64,
+    loaded_bytes: usize,
+    recent_locales: Vec<LanguageIdentifier>,
+}
+
+impl<S: CacheStatus> ResourceCache<S> {
+    pub fn new(policy: CachePolicy) -> Self {
+        Self {
+            policy,
+            entries: FxHashMap::default(),
+            clock: 0,
+            loaded_bytes: 0,
+            recent_locales: vec![],
+        }
+    }
+
+    pub fn get(&self, full_path: &str) -> Option<S> {
+        self.entries.get(full_path)?.status.upgrade()
+    }
+
+    /// Like [`get`](#method.get), but counts as a use of the entry for the
+    /// cache policy.
+    #[cfg(feature = "sync")]
+    pub fn touch(&mut self, full_path: &str) -> Option<S> {
+        self.clock += 1;
+        let last_used = self.clock;
+        let entry = self.entries.get_mut(full_path)?;
+        let status = entry.status.upgrade()?;
+        entry.last_used = last_used;
+        if let CachePolicy::Locales { max_locales } = self.policy {
+            let locale = entry.locale.clone();
+            self.note_locale(&locale, max_locales);
+        }
+        Some(status)
+    }
+
+    pub fn loaded_bytes(&self) -> usize {
+        self.loaded_bytes
+    }
+
+    /// Return the status cached for `full_path`, calling `f` with the id of
+    /// the new entry to create it if there is none, or if its load has been
+    /// abandoned.
+    pub fn lookup<F>(&mut self, full_path: String, locale: &LanguageIdentifier, f: F) -> S
+    where
+        F: FnOnce(u64) -> S,
+    {
+        self.clock += 1;
+        let last_used = self.clock;
+        let abandoned = matches!(
+            self.entries.get(&full_path),
+            Some(entry) if entry.status.upgrade().is_none()
+        );
+        if abandoned {
+            self.remove(&full_path);
+        }
+        let status = match self.entries.entry(full_path) {
+            Entry::Occupied(mut entry) => {
+                let entry = entry.get_mut();
+                entry.last_used = last_used;
+                entry
+                    .status
+                    .upgrade()
+                    .expect("Abandoned entries have been removed")
+            }
+            Entry::Vacant(entry) => {
+                let status = f(last_used);
+                let cached = CachedStatus::new(status.clone());
+                self.loaded_bytes += cached.size();
+                entry.insert(CacheEntry {
+                    id: last_used,
+                    status: cached,
+                    locale: locale.clone(),
+                    stamp: None,
+                    last_used,
+                });
+                status
+            }
+        };
+        if let CachePolicy::Locales { max_locales } = self.policy {
+            self.note_locale(locale, max_locales);
+        }
+        self.evict_over_budget(last_used);
+        status
+    }
+
+    /// Returns the id of the entry for `full_path`, if it is loading.
+    pub fn loading_id(&self, full_path: &str) -> Option<u64> {
+        match self.entries.get(full_path) {
+            Some(entry) if entry.status.is_loading() => Some(entry.id),
+            _ => None,
+        }
+    }
+
+    /// Replace the status of the entry with the given `id`. Returns `false`
+    /// if that entry is no longer in the cache.
+    pub fn update(&mut self, full_path: &str, id: u64, status: S) -> bool {
+        self.clock += 1;
+        let last_used = self.clock;
+        match self.entries.get_mut(full_path) {
+            Some(entry) if entry.id == id => {
+                let status = CachedStatus::new(status);
+                self.loaded_bytes -= entry.status.size();
+                self.loaded_bytes += status.size();
+                entry.status = status;
+                entry.last_used = last_used;
+            }
+            _ => return false,
+        }
+        self.evict_over_budget(last_used);
+        true
+    }
+
+    /// Record the stamp of the resource stored in the entry with the given
+    /// `id`, if that entry is still in the cache.
+    pub fn set_stamp(&mut self, full_path: &str, id: u64, stamp: Option<String>) {
+        match self.entries.get_mut(full_path) {
+            Some(entry) if entry.id == id => entry.stamp = stamp,
+            _ => {}
+        }
+    }
+
+    pub fn remove(&mut self, full_path: &str) {
+        if let Some(entry) = self.entries.remove(full_path) {
+            self.loaded_bytes -= entry.status.size();
+        }
+    }
+
+    pub fn remove_locale(&mut self, locale: &LanguageIdentifier) {
+        self.remove_where(|entry| &entry.locale == locale);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.loaded_bytes = 0;
+    }
+
+    /// Remove all entries for which `predicate` returns `true`.
+    fn remove_where<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&CacheEntry<S>) -> bool,
+    {
+        let loaded_bytes = &mut self.loaded_bytes;
+        self.entries.retain(|_, entry| {
+            if !predicate(entry) {
+                return true;
+            }
+            *loaded_bytes -= entry.status.size();
+            false
+        });
+    }
+
+    fn note_locale(&mut self, locale: &LanguageIdentifier, max_locales: usize) {
+        if self.recent_locales.first() == Some(locale) {
+            return;
+        }
+        if let Some(pos) = self.recent_locales.iter().position(|l| l == locale) {
+            self.recent_locales.remove(pos);
+        }
+        self.recent_locales.insert(0, locale.clone());
+
+        let max_locales = max_locales.max(1);
+        if self.recent_locales.len() > max_locales {
+            let evicted = self.recent_locales.split_off(max_locales);
+            self.remove_where(|entry| {
+                !entry.status.is_loading() && evicted.contains(&entry.locale)
+            });
+        }
+    }
+
+    /// Evict least recently used resources until the cache fits in the
+    /// budget of a `CachePolicy::Lru`. The entry used at `current` is kept.
+    fn evict_over_budget(&mut self, current: u64) {
+        let max_bytes = match self.policy {
+            CachePolicy::Lru { max_bytes } if self.loaded_bytes > max_bytes => max_bytes,
+            _ => return,
+        };
+
+        let mut candidates: Vec<(u64, usize, &str)> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.last_used != current)
+            .filter_map(|(path, entry)| {
+                let size = entry.status.loaded_size()?;
+                Some((entry.last_used, size, path.as_str()))
+            })
+            .collect();
+        candidates.sort_unstable_by_key(|(last_used, ..)| *last_used);
+
+        let mut excess = self.loaded_bytes - max_bytes;
+        let mut evicted = vec![];
+        for (_, size, path) in candidates {
+            if excess == 0 {
+                break;
+            }
+            evicted.push(path.to_string());
+            excess = excess.saturating_sub(size);
+        }
+        for path in evicted {
+            self.remove(&path);
+        }
+    }
+}
+
+impl ResourceCache<ResourceStatus> {
+    /// Returns the full path, locale, resource and stamp of every loaded
+    /// entry.
+    pub fn loaded(
+        &self,
+    ) -> impl Iterator<Item = (&str, &LanguageIdentifier, &RcResource, Option<&str>)> {
+        self.entries
+            .iter()
+            .filter_map(|(full_path, entry)| match &entry.status {
+                CachedStatus::Done(ResourceStatus::Loaded(res)) => Some((
+                    full_path.as_str(),
+                    &entry.locale,
+                    res,
+                    entry.stamp.as_deref(),
+                )),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluent::FluentResource;
+    use std::rc::Rc;
+
+    fn loaded(source: &str) -> ResourceStatus {
+        ResourceStatus::Loaded(Rc::new(
+            FluentResource::try_new(source.to_string()).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn unbounded_keeps_everything() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let mut cache = ResourceCache::new(CachePolicy::Unbounded);
+
+        cache.lookup("a.ftl".into(), &en_us, |_| loaded("a = A"));
+        cache.lookup("b.ftl".into(), &en_us, |_| loaded("b = B"));
+        cache.lookup("c.ftl".into(), &en_us, |_| ResourceStatus::MissingRequired);
+
+        assert!(cache.get("a.ftl").is_some());
+        assert!(cache.get("b.ftl").is_some());
+        assert!(cache.get("c.ftl").is_some());
+        assert_eq!(cache.loaded_bytes(), 10);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let mut cache = ResourceCache::new(CachePolicy::Lru { max_bytes: 10 });
+
+        cache.lookup("a.ftl".into(), &en_us, |_| loaded("a = A"));
+        cache.lookup("b.ftl".into(), &en_us, |_| loaded("b = B"));
+        cache.lookup("missing.ftl".into(), &en_us, |_| {
+            ResourceStatus::MissingOptional
+        });
+        // Touch `a.ftl` so that `b.ftl` becomes the least recently used.
+        cache.lookup("a.ftl".into(), &en_us, |_| unreachable!());
+        cache.lookup("c.ftl".into(), &en_us, |_| loaded("c = C"));
+
+        assert!(cache.get("a.ftl").is_some());
+        assert!(cache.get("b.ftl").is_none());
+        assert!(cache.get("c.ftl").is_some());
+        assert!(cache.get("missing.ftl").is_some());
+        assert_eq!(cache.loaded_bytes(), 10);
+    }
+
+    #[test]
+    fn lru_keeps_loading_entries() {
+        use futures::FutureExt;
+
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let mut cache = ResourceCache::new(CachePolicy::Lru { max_bytes: 5 });
+
+        let future = futures::future::pending().boxed_local().shared();
+        let mut id = 0;
+        cache.lookup("a.ftl".into(), &en_us, |entry_id| {
+            id = entry_id;
+            ResourceStatus::Loading(future)
+        });
+        cache.lookup("b.ftl".into(), &en_us, |_| loaded("b = B"));
+        assert!(cache.update("a.ftl", id, loaded("a = A")));
+
+        assert!(cache.get("a.ftl").is_some());
+        assert!(cache.get("b.ftl").is_none());
+        assert_eq!(cache.loaded_bytes(), 5);
+    }
+
+    #[test]
+    fn locales_evicts_whole_locale() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let pl: LanguageIdentifier = "pl".parse().unwrap();
+        let de: LanguageIdentifier = "de".parse().unwrap();
+        let mut cache = ResourceCache::new(CachePolicy::Locales { max_locales: 2 });
+
+        cache.lookup("en-US/a.ftl".into(), &en_us, |_| loaded("a = A"));
+        cache.lookup("en-US/b.ftl".into(), &en_us, |_| {
+            ResourceStatus::MissingRequired
+        });
+        cache.lookup("pl/a.ftl".into(), &pl, |_| loaded("a = A"));
+        cache.lookup("de/a.ftl".into(), &de, |_| loaded("a = A"));
+
+        assert!(cache.get("en-US/a.ftl").is_none());
+        assert!(cache.get("en-US/b.ftl").is_none());
+        assert!(cache.get("pl/a.ftl").is_some());
+        assert!(cache.get("de/a.ftl").is_some());
+        assert_eq!(cache.loaded_bytes(), 10);
+    }
+
+    #[test]
+    fn update_after_remove() {
+        use futures::FutureExt;
+
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let mut cache = ResourceCache::new(CachePolicy::Unbounded);
+
+        let mut ids = vec![];
+        for _ in 0..2 {
+            let future = futures::future::pending().boxed_local().shared();
+            cache.lookup("a.ftl".into(), &en_us, |id| {
+                ids.push(id);
+                ResourceStatus::Loading(future)
+            });
+            cache.remove_locale(&en_us);
+            assert!(cache.get("a.ftl").is_none());
+        }
+
+        cache.lookup("a.ftl".into(), &en_us, |id| {
+            ids.push(id);
+            ResourceStatus::MissingOptional
+        });
+        // Loads started before the removals must not replace the new entry.
+        assert!(!cache.update("a.ftl", ids[0], loaded("a = A")));
+        assert!(!cache.update("a.ftl", ids[1], loaded("a = A")));
+        assert!(cache.update("a.ftl", ids[2], loaded("a = A")));
+        assert_eq!(cache.loaded_bytes(), 5);
+
+        cache.clear();
+        assert!(cache.get("a.ftl").is_none());
+        assert_eq!(cache.loaded_bytes(), 0);
+    }
+}
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use fluent_fallback::types::ResourceId;
+use futures::future::join_all;
 use std::io;
 
 /// The users of [`FileSource`] implement this trait to provide loading of
@@ -7,6 +8,15 @@ use std::io;
 /// `String`. [`FileSource`] handles the conversion from string representation
 /// into `FluentResource`.
 ///
+/// [`FileSource`] reads resources through [`fetch_bytes_sync`] and
+/// [`fetch_bytes`], which by default call the `String` methods. Implementors
+/// reading raw data can override them instead of decoding it themselves, in
+/// which case [`FileSource`] strips the UTF-8 BOM and reports invalid
+/// UTF-8.
+///
+/// [`fetch_bytes_sync`]: #method.fetch_bytes_sync
+/// [`fetch_bytes`]: #method.fetch_bytes
+///
 /// [`FileSource`]: source/struct.FileSource.html
 #[async_trait(?Send)]
 pub trait FileFetcher {
@@ -27,4 +37,47 @@ pub trait FileFetcher {
     ///
     /// See [`fetch_sync`](#tymethod.fetch_sync)
     async fn fetch(&self, path: &ResourceId) -> io::Result<String>;
+
+    /// Return the raw contents of `path`. This version is blocking.
+    ///
+    /// See [`fetch_bytes`](#method.fetch_bytes).
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_sync(resource_id).map(String::into_bytes)
+    }
+
+    /// Return the raw contents of `path`.
+    ///
+    /// See [`fetch_bytes_sync`](#method.fetch_bytes_sync).
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch(resource_id).await.map(String::into_bytes)
+    }
+
+    /// Return the raw contents of each of `resource_ids`, in the same order.
+    ///
+    /// [`FileSource`] uses it to request the resources needed together at
+    /// once, e.g. for a single round-trip to another process. By default,
+    /// the resources are fetched concurrently with
+    /// [`fetch_bytes`](#method.fetch_bytes).
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        join_all(resource_ids.iter().map(|id| self.fetch_bytes(id))).await
+    }
+
+    /// Return a stamp which changes whenever the contents of `path` do, e.g.
+    /// its size and modification time, without reading it.
+    ///
+    /// A [`StartupCache`](struct.StartupCache.html) only seeds a source with
+    /// a saved resource if its stamp is still the same. By default, there is
+    /// no stamp, leaving the version of the whole cache as the only check.
+    fn stamp(&self, _resource_id: &ResourceId) -> Option<String> {
+        None
+    }
+
+    /// Describe where the last fetch of `path` was served from, e.g. the
+    /// layer of an [`OverlayFileFetcher`], to be included in the errors
+    /// reported about it. By default, there is no such description.
+    ///
+    /// [`OverlayFileFetcher`]: ../fetchers/struct.OverlayFileFetcher.html
+    fn origin(&self, _resource_id: &ResourceId) -> Option<String> {
+        None
+    }
 }
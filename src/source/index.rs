@@ -0,0 +1,117 @@
+use super::PathTemplate;
+use rustc_hash::FxHashSet;
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+use unic_langid::LanguageIdentifier;
+
+/// The list of files available in a `FileSource`.
+///
+/// Besides the full paths, the index keeps a set of paths per locale with the
+/// locale's prefix stripped, so that they can be looked up by the `value` of
+/// a `ResourceId` without building the full path.
+pub(crate) struct SourceIndex {
+    paths: Vec<String>,
+    locales: Vec<FxHashSet<String>>,
+}
+
+impl SourceIndex {
+    /// Build an index of `paths` for the locales whose paths start with the
+    /// corresponding entry of `prefixes`.
+    pub fn new(paths: Vec<String>, prefixes: &[String]) -> Self {
+        let locales = prefixes
+            .iter()
+            .map(|prefix| {
+                paths
+                    .iter()
+                    .filter_map(|path| path.strip_prefix(prefix.as_str()))
+                    .map(|path| path.to_string())
+                    .collect()
+            })
+            .collect();
+        Self { paths, locales }
+    }
+
+    pub fn paths(&self) -> &Vec<String> {
+        &self.paths
+    }
+
+    /// Returns `true` if the locale at `locale_idx` has a file at `path`
+    /// relative to its prefix.
+    pub fn contains(&self, locale_idx: usize, path: &str) -> bool {
+        self.locales[locale_idx].contains(path)
+    }
+}
+
+/// Build the index of a `FileSource` by walking the directories of its
+/// `locales` under `root`.
+///
+/// The `pre_path` template is expanded for every locale and interpreted
+/// relative to `root`. The returned paths are
+/// sorted and ready to be passed to
+/// [`FileSource::new_with_index`](struct.FileSource.html#method.new_with_index).
+/// Locales without a directory don't contribute any paths.
+pub fn build_index_from_directory(
+    root: &Path,
+    locales: &[LanguageIdentifier],
+    pre_path: &PathTemplate,
+) -> io::Result<Vec<String>> {
+    let mut index = vec![];
+    for locale in locales {
+        let prefix = pre_path.expand(locale);
+        let dir = root.join(prefix.trim_start_matches('/'));
+        match walk_directory(&dir, &prefix, &mut index) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            result => result?,
+        }
+    }
+    index.sort();
+    index.dedup();
+    Ok(index)
+}
+
+fn walk_directory(dir: &Path, prefix: &str, index: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_str().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Non UTF-8 file name in {}", dir.display()),
+            )
+        })?;
+        let path = format!("{}{}", prefix, name);
+        if entry.file_type()?.is_dir() {
+            walk_directory(&entry.path(), &format!("{}/", path), index)?;
+        } else {
+            index.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Read an index written by [`write_index`](fn.write_index.html).
+///
+/// The format is one path per line. Empty lines and lines starting with
+/// `#` are ignored.
+pub fn read_index<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut index = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            index.push(line.to_string());
+        }
+    }
+    Ok(index)
+}
+
+/// Write an index in the format read by [`read_index`](fn.read_index.html).
+pub fn write_index<W: Write>(mut writer: W, index: &[String]) -> io::Result<()> {
+    for path in index {
+        writeln!(writer, "{}", path)?;
+    }
+    writer.flush()
+}
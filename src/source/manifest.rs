@@ -0,0 +1,189 @@
+#[cfg(feature = "checksums")]
+use crate::errors::L10nRegistryError;
+#[cfg(feature = "checksums")]
+use fluent_fallback::types::ResourceId;
+#[cfg(feature = "checksums")]
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+};
+#[cfg(feature = "checksums")]
+use unic_langid::LanguageIdentifier;
+
+pub type Sha256Hash = [u8; 32];
+
+/// The expected SHA-256 hash of every file of a `FileSource`, keyed by full
+/// path, i.e. the path passed to the `FileFetcher`.
+///
+/// A source with a manifest rejects any resource whose contents don't match
+/// its hash, as well as any resource the manifest doesn't list, treating it
+/// as missing and reporting a [`L10nRegistryError::ChecksumMismatch`].
+/// Manifests can always be read and written, but hashing contents and
+/// verifying resources requires the `checksums` feature.
+///
+/// [`L10nRegistryError::ChecksumMismatch`]: ../errors/enum.L10nRegistryError.html#variant.ChecksumMismatch
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    hashes: BTreeMap<String, Sha256Hash>,
+}
+
+impl ChecksumManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash `contents` and store the result for `path`.
+    #[cfg(feature = "checksums")]
+    pub fn insert_contents<P: ToString>(&mut self, path: P, contents: &[u8]) {
+        self.insert(path, sha256(contents));
+    }
+
+    pub fn insert<P: ToString>(&mut self, path: P, hash: Sha256Hash) {
+        self.hashes.insert(path.to_string(), hash);
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Sha256Hash> {
+        self.hashes.get(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Whether `contents` match the hash stored for `path`.
+    #[cfg(feature = "checksums")]
+    pub(crate) fn matches(&self, path: &str, contents: &[u8]) -> bool {
+        self.get(path) == Some(&sha256(contents))
+    }
+
+    /// Check the fetched `contents` of `resource_id` against the manifest,
+    /// returning the error to report if they don't match.
+    #[cfg(feature = "checksums")]
+    pub(crate) fn verify(
+        &self,
+        resource_id: &ResourceId,
+        locale: &LanguageIdentifier,
+        contents: &[u8],
+    ) -> Option<L10nRegistryError> {
+        let actual = sha256(contents);
+        let expected = self.get(&resource_id.value);
+        if expected == Some(&actual) {
+            return None;
+        }
+        Some(L10nRegistryError::ChecksumMismatch {
+            resource_id: resource_id.clone(),
+            locale: locale.clone(),
+            expected: expected.map(to_hex),
+            actual: to_hex(&actual),
+        })
+    }
+
+    /// Read a manifest in the format of `sha256sum`: one hex-encoded hash
+    /// and a path per line, separated by whitespace. Empty lines and lines
+    /// starting with `#` are ignored.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] on malformed lines.
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut manifest = Self::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid checksum manifest entry on line {}", idx + 1),
+                )
+            };
+            let mut parts = line.splitn(2, char::is_whitespace);
+            let hash = parts.next().and_then(from_hex).ok_or_else(invalid)?;
+            let path = parts.next().map(str::trim_start).ok_or_else(invalid)?;
+            // `sha256sum` marks files read in binary mode with a `*`.
+            let path = path.strip_prefix('*').unwrap_or(path);
+            manifest.insert(path, hash);
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest in the format read by [`read`](#method.read),
+    /// sorted by path.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (path, hash) in &self.hashes {
+            writeln!(writer, "{}  {}", to_hex(hash), path)?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(feature = "checksums")]
+fn sha256(contents: &[u8]) -> Sha256Hash {
+    Sha256::digest(contents).into()
+}
+
+fn to_hex(hash: &Sha256Hash) -> String {
+    hash.iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+fn from_hex(hex: &str) -> Option<Sha256Hash> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0; 32];
+    for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+#[cfg(feature = "checksums")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_roundtrip() {
+        let hash = sha256(b"");
+        let hex = to_hex(&hash);
+        assert_eq!(
+            hex,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(from_hex(&hex), Some(hash));
+        assert_eq!(from_hex(&hex[1..]), None);
+        assert_eq!(from_hex(&hex.replace('e', "g")), None);
+    }
+
+    #[test]
+    fn read_write() {
+        let mut manifest = ChecksumManifest::new();
+        manifest.insert_contents("pl/menu.ftl", b"menu-file = Plik\n");
+        manifest.insert_contents("en-US/menu.ftl", b"menu-file = File\n");
+
+        let mut text = vec![];
+        manifest.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with(&format!(
+            "{}  en-US/menu.ftl\n",
+            to_hex(&sha256(b"menu-file = File\n"))
+        )));
+
+        let text = format!("# Generated\n\n{}", text.replace("  pl/", " *pl/"));
+        assert_eq!(ChecksumManifest::read(text.as_bytes()).unwrap(), manifest);
+
+        let err = ChecksumManifest::read(&b"abc menu.ftl\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,27 +1,60 @@
+mod builder;
+mod cache;
 mod fetcher;
+mod index;
+mod manifest;
+mod retry;
+mod startup_cache;
+mod stats;
+mod template;
+pub use builder::FileSourceBuilder;
+pub use cache::CachePolicy;
 pub use fetcher::FileFetcher;
 pub use fluent_fallback::types::{ResourceId, ToResourceId};
-
-use crate::env::ErrorReporter;
-use crate::errors::L10nRegistryError;
+pub use index::{build_index_from_directory, read_index, write_index};
+pub use manifest::{ChecksumManifest, Sha256Hash};
+pub use retry::RetryPolicy;
+pub use startup_cache::StartupCache;
+pub use stats::SourceStats;
+pub use template::PathTemplate;
+
+use crate::env::{ErrorReporter, Timer};
+use crate::errors::{L10nRegistryError, L10nRegistrySetupError};
 use crate::fluent::FluentResource;
 
+use std::sync::Arc;
 use std::{
     borrow::Borrow,
     cell::RefCell,
+    collections::HashMap,
     fmt,
     hash::{Hash, Hasher},
+    io,
     pin::Pin,
     rc::Rc,
     task::Poll,
+    time::{Duration, Instant},
 };
 
-use futures::{future::Shared, Future, FutureExt};
+#[cfg(feature = "sync")]
+pub(crate) use cache::CacheStatus;
+pub(crate) use cache::ResourceCache;
+use futures::{
+    channel::oneshot,
+    future::{self, Either, Shared},
+    Future, FutureExt,
+};
+pub(crate) use index::SourceIndex;
+use retry::FetchPolicy;
 use rustc_hash::FxHashMap;
+use startup_cache::StartupCacheEntry;
+pub(crate) use stats::StatsCounter;
 use unic_langid::LanguageIdentifier;
 
 pub type RcResource = Rc<FluentResource>;
 
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
 /// An option type whose None variant is either optional or required.
 ///
 /// This behaves similarly to the standard-library [`Option`] type
@@ -35,6 +68,14 @@ pub enum ResourceOption {
     MissingOptional,
     /// A missing required resource.
     MissingRequired,
+    /// A resource which exists, but couldn't be fetched. This is treated as
+    /// missing, but the error has been reported as a
+    /// [`L10nRegistryError::FetchError`](../errors/enum.L10nRegistryError.html#variant.FetchError).
+    FetchFailed { required: bool, kind: io::ErrorKind },
+    /// A resource which is being loaded asynchronously, returned by a sync
+    /// load which isn't allowed to overload it. The future resolves once
+    /// the async load completes.
+    Pending(ResourceFuture),
 }
 
 impl ResourceOption {
@@ -49,6 +90,14 @@ impl ResourceOption {
         }
     }
 
+    /// Creates a [`ResourceOption::FetchFailed`] for the given [`ResourceId`].
+    pub fn fetch_failed(resource_id: &ResourceId, kind: io::ErrorKind) -> Self {
+        Self::FetchFailed {
+            required: resource_id.is_required(),
+            kind,
+        }
+    }
+
     /// Returns [`true`] if this option contains a recource, otherwise [`false`].
     pub fn is_some(&self) -> bool {
         matches!(self, Self::Some(_))
@@ -56,12 +105,29 @@ impl ResourceOption {
 
     /// Resource [`true`] if this option is missing a resource of any type, otherwise [`false`].
     pub fn is_none(&self) -> bool {
-        matches!(self, Self::MissingOptional | Self::MissingRequired)
+        matches!(
+            self,
+            Self::MissingOptional | Self::MissingRequired | Self::FetchFailed { .. }
+        )
     }
 
     /// Returns [`true`] if this option is missing a required resource, otherwise [`false`].
     pub fn is_required_and_missing(&self) -> bool {
-        matches!(self, Self::MissingRequired)
+        matches!(
+            self,
+            Self::MissingRequired | Self::FetchFailed { required: true, .. }
+        )
+    }
+
+    /// Returns [`true`] if the resource is missing because fetching it failed,
+    /// otherwise [`false`].
+    pub fn is_fetch_failed(&self) -> bool {
+        matches!(self, Self::FetchFailed { .. })
+    }
+
+    /// Returns [`true`] if the resource is still being loaded, otherwise [`false`].
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending(_))
     }
 }
 
@@ -81,18 +147,42 @@ pub enum ResourceStatus {
     /// The resource is missing.  Don't bother trying to fetch.
     MissingRequired,
     MissingOptional,
+    /// The resource couldn't be fetched. Don't bother trying again.
+    FetchFailed {
+        required: bool,
+        kind: io::ErrorKind,
+    },
     /// The resource is loading and future will deliver the result.
     Loading(ResourceFuture),
     /// The resource is loaded and parsed.
     Loaded(RcResource),
 }
 
+impl ResourceStatus {
+    /// Match a cached miss or failure to whether `resource_id` is required,
+    /// since the cache entry may have been created by a request of the other
+    /// type.
+    fn for_resource(self, resource_id: &ResourceId) -> Self {
+        match self {
+            Self::MissingRequired | Self::MissingOptional => {
+                ResourceOption::missing_resource(resource_id).into()
+            }
+            Self::FetchFailed { kind, .. } => {
+                ResourceOption::fetch_failed(resource_id, kind).into()
+            }
+            status => status,
+        }
+    }
+}
+
 impl From<ResourceOption> for ResourceStatus {
     fn from(input: ResourceOption) -> Self {
         match input {
             ResourceOption::Some(res) => Self::Loaded(res),
             ResourceOption::MissingOptional => Self::MissingOptional,
             ResourceOption::MissingRequired => Self::MissingRequired,
+            ResourceOption::FetchFailed { required, kind } => Self::FetchFailed { required, kind },
+            ResourceOption::Pending(future) => Self::Loading(future),
         }
     }
 }
@@ -108,6 +198,11 @@ impl Future for ResourceStatus {
         match this {
             MissingRequired => ResourceOption::MissingRequired.into(),
             MissingOptional => ResourceOption::MissingOptional.into(),
+            FetchFailed { required, kind } => ResourceOption::FetchFailed {
+                required: *required,
+                kind: *kind,
+            }
+            .into(),
             Loaded(res) => ResourceOption::Some(res.clone()).into(),
             Loading(res) => Pin::new(res).poll(cx),
         }
@@ -122,21 +217,36 @@ pub struct FileSource {
     /// Name of the FileSource, e.g. "browser"
     pub name: String,
     /// Pre-formatted path for the FileSource, e.g. "/browser/data/locale/{locale}/"
-    pub pre_path: String,
+    /// It is expanded for each locale when the source is created.
+    /// See [`PathTemplate`](struct.PathTemplate.html) for the placeholders it
+    /// may contain.
+    pre_path: String,
     /// Metasource name for the FileSource, e.g. "app", "langpack"
     /// Only sources from the same metasource are passed into the solver.
     pub metasource: String,
     /// The locales for which data is present in the FileSource, e.g. ["en-US", "pl"]
     locales: Vec<LanguageIdentifier>,
+    /// The parsed `pre_path`.
+    template: Rc<PathTemplate>,
+    /// The `pre_path` expanded for each of the `locales`.
+    prefixes: Rc<Vec<String>>,
     shared: Rc<Inner>,
-    index: Option<Vec<String>>,
-    pub options: FileSourceOptions,
+    index: Option<Rc<SourceIndex>>,
+    /// Read when the source is created, like the `pre_path`.
+    options: FileSourceOptions,
 }
 
 struct Inner {
     fetcher: Box<dyn FileFetcher>,
+    fetch_policy: FetchPolicy,
     error_reporter: Option<RefCell<Box<dyn ErrorReporter>>>,
-    entries: RefCell<FxHashMap<String, ResourceStatus>>,
+    entries: RefCell<ResourceCache>,
+    /// Senders allowing a sync load to resolve the pending async load of the
+    /// same resource, keyed by the id of its cache entry.
+    pending: RefCell<FxHashMap<u64, oneshot::Sender<ResourceOption>>>,
+    stats: StatsCounter,
+    record_stamps: bool,
+    checksums: Option<Arc<ChecksumManifest>>,
 }
 
 impl fmt::Display for FileSource {
@@ -159,21 +269,69 @@ impl Hash for FileSource {
     }
 }
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Default)]
 pub struct FileSourceOptions {
     pub allow_override: bool,
+    /// Limits on the resources kept in the cache. Read when the source is
+    /// created.
+    pub cache_policy: CachePolicy,
+    /// Values of the user-defined placeholders in the `pre_path`, e.g.
+    /// `{platform}` or `{channel}`.
+    pub variables: HashMap<String, String>,
+    /// Locales served by the files of another locale of the source, e.g.
+    /// `es` → `es-ES`. Aliases whose target isn't in the source's locales
+    /// are ignored. Errors reported while loading the resources of an alias
+    /// name its target, and bundles built from them list it after the
+    /// requested locale.
+    pub aliases: HashMap<LanguageIdentifier, LanguageIdentifier>,
+    /// The time after which an async fetch fails with
+    /// [`io::ErrorKind::TimedOut`], causing the resource to be treated as
+    /// missing for that request. Timeouts are not cached, so the next request
+    /// fetches the resource again. Requires a timer, see
+    /// [`set_timer`](struct.FileSource.html#method.set_timer); registering a
+    /// source without one fails with
+    /// [`L10nRegistrySetupError::MissingTimer`](../errors/enum.L10nRegistrySetupError.html#variant.MissingTimer).
+    /// Read when the source is created.
+    pub fetch_timeout: Option<Duration>,
+    /// Read when the source is created.
+    pub retry_policy: RetryPolicy,
+    /// Whether to read the [`FileFetcher::stamp`] of each resource before it
+    /// is fetched, so that [`FileSource::save_startup_cache`] can save it.
+    /// Without it, resources are saved without stamps, and the ones of
+    /// fetchers which provide stamps can't be seeded from the startup cache.
+    /// Read when the source is created.
+    ///
+    /// [`FileFetcher::stamp`]: trait.FileFetcher.html#method.stamp
+    /// [`FileSource::save_startup_cache`]: struct.FileSource.html#method.save_startup_cache
+    pub record_stamps: bool,
+    /// The expected hashes of the files of the source. Resources which
+    /// don't match are treated as missing. Requires the `checksums` feature,
+    /// without which creating the source fails with
+    /// [`L10nRegistrySetupError::UnsupportedOption`](../errors/enum.L10nRegistrySetupError.html#variant.UnsupportedOption).
+    /// Read when the source is created.
+    pub checksums: Option<Arc<ChecksumManifest>>,
 }
 
-impl Default for FileSourceOptions {
-    fn default() -> Self {
-        Self {
-            allow_override: false,
+impl FileSourceOptions {
+    /// Returns an error if the options use a feature which isn't enabled.
+    pub(crate) fn check_features(&self, name: &str) -> Result<(), L10nRegistrySetupError> {
+        if cfg!(not(feature = "checksums")) && self.checksums.is_some() {
+            return Err(L10nRegistrySetupError::UnsupportedOption {
+                name: name.to_string(),
+                option: "checksums".to_string(),
+            });
         }
+        Ok(())
     }
 }
 
 impl FileSource {
     /// Create a `FileSource` using the provided [`FileFetcher`](../trait.FileFetcher.html).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pre_path` contains an unknown placeholder. Use
+    /// [`try_new`](#method.try_new) to handle the error instead.
     pub fn new(
         name: String,
         metasource: Option<String>,
@@ -182,21 +340,29 @@ impl FileSource {
         options: FileSourceOptions,
         fetcher: impl FileFetcher + 'static,
     ) -> Self {
-        FileSource {
-            name,
-            metasource: metasource.unwrap_or_default(),
-            pre_path,
-            locales,
-            index: None,
-            shared: Rc::new(Inner {
-                entries: RefCell::new(FxHashMap::default()),
-                fetcher: Box::new(fetcher),
-                error_reporter: None,
-            }),
-            options,
-        }
+        Self::try_new(name, metasource, locales, pre_path, options, fetcher).unwrap()
     }
 
+    /// Create a `FileSource`, or return an error if `pre_path` contains an
+    /// unknown placeholder.
+    pub fn try_new(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        Self::with_optional_index(name, metasource, locales, pre_path, options, fetcher, None)
+    }
+
+    /// Create a `FileSource` with the list of full paths available in it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pre_path` contains an unknown placeholder. Use
+    /// [`try_new_with_index`](#method.try_new_with_index) to handle the
+    /// error instead.
     pub fn new_with_index(
         name: String,
         metasource: Option<String>,
@@ -206,32 +372,201 @@ impl FileSource {
         fetcher: impl FileFetcher + 'static,
         index: Vec<String>,
     ) -> Self {
-        FileSource {
+        Self::try_new_with_index(name, metasource, locales, pre_path, options, fetcher, index)
+            .unwrap()
+    }
+
+    /// Create a `FileSource` with the list of full paths available in it, or
+    /// return an error if `pre_path` contains an unknown placeholder.
+    pub fn try_new_with_index(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+        index: Vec<String>,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        Self::with_optional_index(
+            name,
+            metasource,
+            locales,
+            pre_path,
+            options,
+            fetcher,
+            Some(index),
+        )
+    }
+
+    fn with_optional_index(
+        name: String,
+        metasource: Option<String>,
+        locales: Vec<LanguageIdentifier>,
+        pre_path: String,
+        options: FileSourceOptions,
+        fetcher: impl FileFetcher + 'static,
+        index: Option<Vec<String>>,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        options.check_features(&name)?;
+        let template = PathTemplate::new(&pre_path, &options.variables)?;
+        let prefixes: Vec<String> = locales
+            .iter()
+            .map(|locale| template.expand(locale))
+            .collect();
+        let index = index.map(|index| Rc::new(SourceIndex::new(index, &prefixes)));
+        Ok(FileSource {
             name,
             metasource: metasource.unwrap_or_default(),
             pre_path,
             locales,
-            index: Some(index),
+            template: Rc::new(template),
+            prefixes: Rc::new(prefixes),
+            index,
             shared: Rc::new(Inner {
-                entries: RefCell::new(FxHashMap::default()),
+                entries: RefCell::new(ResourceCache::new(options.cache_policy.clone())),
                 fetcher: Box::new(fetcher),
+                fetch_policy: FetchPolicy {
+                    timeout: options.fetch_timeout,
+                    retry: options.retry_policy.clone(),
+                    timer: None,
+                },
                 error_reporter: None,
+                pending: RefCell::new(FxHashMap::default()),
+                stats: StatsCounter::default(),
+                record_stamps: options.record_stamps,
+                checksums: options.checksums.clone(),
             }),
             options,
-        }
+        })
     }
 
     pub fn set_reporter(&mut self, reporter: impl ErrorReporter + 'static) {
-        let mut shared = Rc::get_mut(&mut self.shared).unwrap();
-        shared.error_reporter = Some(RefCell::new(Box::new(reporter)));
+        self.set_boxed_reporter(Box::new(reporter));
+    }
+
+    fn set_boxed_reporter(&mut self, reporter: Box<dyn ErrorReporter>) {
+        let shared = Rc::get_mut(&mut self.shared).unwrap();
+        shared.error_reporter = Some(RefCell::new(reporter));
+    }
+
+    /// Set the timer used for the `fetch_timeout` and the backoff of the
+    /// `retry_policy` of the source's options.
+    pub fn set_timer(&mut self, timer: impl Timer + 'static) {
+        self.set_boxed_timer(Box::new(timer));
+    }
+
+    fn set_boxed_timer(&mut self, timer: Box<dyn Timer>) {
+        let shared = Rc::get_mut(&mut self.shared).unwrap();
+        shared.fetch_policy.timer = Some(timer);
+    }
+
+    /// Returns an error if the source has a `fetch_timeout` but no timer to
+    /// enforce it, which would otherwise silently disable the timeout.
+    pub(crate) fn check_timer(&self) -> Result<(), L10nRegistrySetupError> {
+        let policy = &self.shared.fetch_policy;
+        if policy.timeout.is_some() && policy.timer.is_none() {
+            return Err(L10nRegistrySetupError::MissingTimer {
+                name: self.name.clone(),
+            });
+        }
+        Ok(())
     }
 }
 
-fn calculate_pos_in_source(source: &str, idx: usize) -> (usize, usize) {
+/// The outcome of parsing a fetched resource.
+pub(crate) enum Parsed {
+    Missing,
+    Failed(io::ErrorKind),
+    Resource(FluentResource),
+}
+
+/// Parse the result of fetching `resource_id` for the sources of both this
+/// module and the [`concurrent`](../concurrent/index.html) one, counting it
+/// in `stats` and passing any errors to `report`.
+///
+/// A resource which is not found is missing, while any other fetch error is
+/// reported as a `L10nRegistryError::FetchError` naming the `origin` of the
+/// resource. A resource rejected by the `checksums` is reported and treated
+/// as missing. A UTF-8 BOM is stripped, and invalid UTF-8 is reported as a
+/// `L10nRegistryError::InvalidEncoding` and treated as a failed fetch.
+#[cfg_attr(not(feature = "checksums"), allow(unused_variables))]
+pub(crate) fn parse_fetched(
+    locale: &LanguageIdentifier,
+    resource_id: &ResourceId,
+    source: io::Result<Vec<u8>>,
+    stats: &StatsCounter,
+    checksums: Option<&ChecksumManifest>,
+    origin: &dyn Fn() -> Option<String>,
+    report: &dyn Fn(Vec<L10nRegistryError>),
+) -> Parsed {
+    let mut source = match source {
+        Ok(source) => source,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Parsed::Missing,
+        Err(err) => {
+            report(vec![L10nRegistryError::FetchError {
+                resource_id: resource_id.clone(),
+                locale: locale.clone(),
+                kind: err.kind(),
+                message: err.to_string(),
+                origin: origin(),
+            }]);
+            return Parsed::Failed(err.kind());
+        }
+    };
+    stats.update(|stats| stats.bytes_loaded += source.len() as u64);
+    #[cfg(feature = "checksums")]
+    if let Some(checksums) = checksums {
+        if let Some(err) = checksums.verify(resource_id, locale, &source) {
+            report(vec![err]);
+            return Parsed::Missing;
+        }
+    }
+    if source.starts_with(UTF8_BOM) {
+        source.drain(..UTF8_BOM.len());
+    }
+    let source = match String::from_utf8(source) {
+        Ok(source) => source,
+        Err(err) => {
+            report(vec![L10nRegistryError::InvalidEncoding {
+                resource_id: resource_id.clone(),
+                locale: locale.clone(),
+                valid_up_to: err.utf8_error().valid_up_to(),
+            }]);
+            return Parsed::Failed(io::ErrorKind::InvalidData);
+        }
+    };
+    let start = Instant::now();
+    let (res, errors) = match FluentResource::try_new(source) {
+        Ok(res) => (res, vec![]),
+        Err((res, errors)) => (res, errors),
+    };
+    let elapsed = start.elapsed();
+    stats.update(|stats| {
+        stats.parse_errors += errors.len() as u64;
+        stats.parse_time += elapsed;
+    });
+    if !errors.is_empty() {
+        let origin = origin();
+        report(
+            errors
+                .into_iter()
+                .map(|e| L10nRegistryError::FluentError {
+                    resource_id: resource_id.clone(),
+                    loc: Some(calculate_pos_in_source(res.source(), e.pos.start)),
+                    error: e.into(),
+                    origin: origin.clone(),
+                })
+                .collect(),
+        );
+    }
+    Parsed::Resource(res)
+}
+
+pub(crate) fn calculate_pos_in_source(source: &str, idx: usize) -> (usize, usize) {
     let mut ptr = 0;
     let mut result = (1, 1);
     for line in source.lines() {
-        let bytes = line.as_bytes().len();
+        let bytes = line.len();
         if ptr + bytes < idx {
             ptr += bytes + 1;
             result.0 += 1;
@@ -244,43 +579,57 @@ fn calculate_pos_in_source(source: &str, idx: usize) -> (usize, usize) {
 }
 
 impl FileSource {
-    fn get_path(&self, locale: &LanguageIdentifier, resource_id: &ResourceId) -> String {
-        format!(
-            "{}{}",
-            self.pre_path.replace("{locale}", &locale.to_string()),
-            resource_id.value,
-        )
+    /// Returns the position of the locale whose files serve `locale`.
+    fn locale_idx(&self, locale: &LanguageIdentifier) -> Option<usize> {
+        self.locales.iter().position(|l| l == locale).or_else(|| {
+            let target = self.options.aliases.get(locale)?;
+            self.locales.iter().position(|l| l == target)
+        })
     }
 
-    fn fetch_sync(&self, resource_id: &ResourceId) -> ResourceOption {
-        self.shared
-            .fetcher
-            .fetch_sync(resource_id)
-            .ok()
-            .map(|source| match FluentResource::try_new(source) {
-                Ok(res) => ResourceOption::Some(Rc::new(res)),
-                Err((res, errors)) => {
-                    if let Some(reporter) = &self.shared.error_reporter {
-                        reporter.borrow().report_errors(
-                            errors
-                                .into_iter()
-                                .map(|e| L10nRegistryError::FluentError {
-                                    resource_id: resource_id.clone(),
-                                    loc: Some(calculate_pos_in_source(res.source(), e.pos.start)),
-                                    error: e.into(),
-                                })
-                                .collect(),
-                        );
-                    }
-                    ResourceOption::Some(Rc::new(res))
-                }
-            })
-            .unwrap_or_else(|| ResourceOption::missing_resource(resource_id))
+    /// Returns the locale from `locales` whose files serve `locale`, which is
+    /// either `locale` itself or the target of its alias.
+    pub fn resolve_locale(&self, locale: &LanguageIdentifier) -> Option<&LanguageIdentifier> {
+        self.locale_idx(locale).map(|idx| &self.locales[idx])
+    }
+
+    pub(crate) fn get_path(&self, locale: &LanguageIdentifier, resource_id: &ResourceId) -> String {
+        match self.locale_idx(locale) {
+            Some(locale_idx) => {
+                let prefix = &self.prefixes[locale_idx];
+                let mut path = String::with_capacity(prefix.len() + resource_id.value.len());
+                path.push_str(prefix);
+                path.push_str(&resource_id.value);
+                path
+            }
+            None => format!("{}{}", self.template.expand(locale), resource_id.value),
+        }
+    }
+
+    fn fetch_sync(&self, locale: &LanguageIdentifier, resource_id: &ResourceId) -> Fetched {
+        let stamp = self.shared.stamp(resource_id);
+        let source = self
+            .shared
+            .stats
+            .time_fetch(1, || self.shared.fetcher.fetch_bytes_sync(resource_id));
+        Fetched {
+            resource: self.shared.parse_resource(locale, resource_id, source),
+            stamp,
+        }
     }
 
     /// Attempt to synchronously fetch resource for the combination of `locale`
     /// and `path`. Returns `Some(ResourceResult)` if the resource is available,
     /// else `None`.
+    ///
+    /// If the resource is being loaded asynchronously, `overload` decides
+    /// whether to load it synchronously anyway, resolving the pending async
+    /// load with the result. Otherwise, a
+    /// [`L10nRegistryError::PendingAsyncLoad`] is reported and
+    /// [`ResourceOption::Pending`] is returned, which the caller may block on
+    /// with an executor of its choice.
+    ///
+    /// [`L10nRegistryError::PendingAsyncLoad`]: ../errors/enum.L10nRegistryError.html#variant.PendingAsyncLoad
     pub fn fetch_file_sync(
         &self,
         locale: &LanguageIdentifier,
@@ -289,41 +638,45 @@ impl FileSource {
     ) -> ResourceOption {
         use ResourceStatus::*;
 
-        if self.has_file(locale, resource_id) == Some(false) {
+        if !self.may_have_file(locale, resource_id) {
             return ResourceOption::missing_resource(resource_id);
         }
 
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
         let full_path_id = self
             .get_path(locale, resource_id)
             .to_resource_id(resource_id.resource_type);
 
-        let res = self.shared.lookup_resource(full_path_id.clone(), || {
-            self.fetch_sync(&full_path_id).into()
-        });
+        let res = self
+            .shared
+            .lookup_resource(full_path_id.clone(), locale, |_| {
+                let fetched = self.fetch_sync(locale, &full_path_id);
+                (fetched.resource.into(), fetched.stamp)
+            })
+            .for_resource(resource_id);
 
         match res {
             MissingRequired => ResourceOption::MissingRequired,
             MissingOptional => ResourceOption::MissingOptional,
+            FetchFailed { required, kind } => ResourceOption::FetchFailed { required, kind },
             Loaded(res) => ResourceOption::Some(res),
             Loading(..) if overload => {
                 // A sync load has been requested for the same resource that has
-                // a pending async load in progress. How do we handle this?
-                //
-                // Ideally, we would sync load and resolve all the pending
-                // futures with the result. With the current Futures and
-                // combinators, it's unclear how to proceed. One potential
-                // solution is to store a oneshot::Sender and
-                // Shared<oneshot::Receiver>. When the async loading future
-                // resolves it would check that the state is still `Loading`,
-                // and if so, send the result. The sync load would do the same
-                // send on the oneshot::Sender.
-                //
-                // For now, we warn and return the resource, paying the cost of
-                // duplication of the resource.
-                self.fetch_sync(&full_path_id)
+                // a pending async load in progress. Load it synchronously and
+                // hand the result over to the pending futures, so that the
+                // resource is only fetched and parsed once.
+                let fetched = self.fetch_sync(locale, &full_path_id);
+                self.shared
+                    .resolve_pending(&full_path_id.value, fetched.clone());
+                fetched.resource
             }
-            Loading(..) => {
-                panic!("[l10nregistry] Attempting to synchronously load file {} while it's being loaded asynchronously.", &full_path_id.value);
+            Loading(future) => {
+                self.shared
+                    .report_errors(vec![L10nRegistryError::PendingAsyncLoad {
+                        locale: locale.clone(),
+                        resource_id: resource_id.clone(),
+                    }]);
+                ResourceOption::Pending(future)
             }
         }
     }
@@ -336,20 +689,127 @@ impl FileSource {
         locale: &LanguageIdentifier,
         resource_id: &ResourceId,
     ) -> ResourceStatus {
-        use ResourceStatus::*;
-
-        if self.has_file(locale, resource_id) == Some(false) {
+        if !self.may_have_file(locale, resource_id) {
             return ResourceOption::missing_resource(resource_id).into();
         }
 
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
         let full_path_id = self
             .get_path(locale, resource_id)
             .to_resource_id(resource_id.resource_type);
 
-        self.shared.lookup_resource(full_path_id.clone(), || {
-            let shared = self.shared.clone();
-            Loading(read_resource(full_path_id, shared).boxed_local().shared())
-        })
+        self.shared
+            .lookup_resource(full_path_id.clone(), locale, |entry_id| {
+                let read = read_resource(full_path_id.clone(), locale.clone(), self.shared.clone());
+                (self.start_loading(full_path_id, entry_id, read), None)
+            })
+            .for_resource(resource_id)
+    }
+
+    /// Attempt to fetch several resources for `locale`, returning a
+    /// [`ResourceStatus`](enum.ResourceStatus.html) for each of them.
+    ///
+    /// Unlike calling [`fetch_file`](#method.fetch_file) for each resource,
+    /// the ones which are neither cached nor being loaded are requested with
+    /// a single [`FileFetcher::fetch_many`](trait.FileFetcher.html#method.fetch_many)
+    /// call.
+    pub fn fetch_files(
+        &self,
+        locale: &LanguageIdentifier,
+        resource_ids: &[ResourceId],
+    ) -> Vec<ResourceStatus> {
+        let full_path_ids: Vec<Option<ResourceId>> = resource_ids
+            .iter()
+            .map(|resource_id| {
+                if !self.may_have_file(locale, resource_id) {
+                    None
+                } else {
+                    Some(
+                        self.get_path(locale, resource_id)
+                            .to_resource_id(resource_id.resource_type),
+                    )
+                }
+            })
+            .collect();
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
+
+        let mut batch = FxHashMap::default();
+        let mut batch_ids = vec![];
+        {
+            let entries = self.shared.entries.borrow();
+            for full_path_id in full_path_ids.iter().flatten() {
+                if entries.get(&full_path_id.value).is_none()
+                    && !batch.contains_key(&full_path_id.value)
+                {
+                    batch.insert(full_path_id.value.clone(), batch_ids.len());
+                    batch_ids.push(full_path_id.clone());
+                }
+            }
+        }
+        let read_batch = if batch_ids.len() > 1 {
+            Some(
+                read_resources(batch_ids, locale.clone(), self.shared.clone())
+                    .boxed_local()
+                    .shared(),
+            )
+        } else {
+            None
+        };
+
+        resource_ids
+            .iter()
+            .zip(full_path_ids)
+            .map(|(resource_id, full_path_id)| {
+                let full_path_id = match full_path_id {
+                    Some(full_path_id) => full_path_id,
+                    None => return ResourceOption::missing_resource(resource_id).into(),
+                };
+                self.shared
+                    .lookup_resource(full_path_id.clone(), locale, |entry_id| {
+                        let status = match (batch.get(&full_path_id.value), &read_batch) {
+                            (Some(&idx), Some(read_batch)) => {
+                                let read = read_batch
+                                    .clone()
+                                    .map(move |resources| resources[idx].clone());
+                                self.start_loading(full_path_id, entry_id, read)
+                            }
+                            _ => {
+                                let read = read_resource(
+                                    full_path_id.clone(),
+                                    locale.clone(),
+                                    self.shared.clone(),
+                                );
+                                self.start_loading(full_path_id, entry_id, read)
+                            }
+                        };
+                        (status, None)
+                    })
+                    .for_resource(resource_id)
+            })
+            .collect()
+    }
+
+    /// Create the `Loading` status of the cache entry `entry_id`, resolved by
+    /// `read` unless a sync load of the same resource completes first.
+    ///
+    /// The cache only keeps a weak reference to the returned future, so if
+    /// all of its clones are dropped before it completes, the load is
+    /// cancelled and the entry removed, allowing the resource to be fetched
+    /// again.
+    fn start_loading(
+        &self,
+        full_path_id: ResourceId,
+        entry_id: u64,
+        read: impl Future<Output = Fetched> + 'static,
+    ) -> ResourceStatus {
+        let (sender, receiver) = oneshot::channel();
+        self.shared.pending.borrow_mut().insert(entry_id, sender);
+        let guard = LoadGuard {
+            resource_id: full_path_id,
+            entry_id,
+            shared: self.shared.clone(),
+        };
+        ResourceStatus::Loading(load_resource(guard, receiver, read).boxed_local().shared())
     }
 
     /// Determine if the `FileSource` has a loaded resource for the combination
@@ -362,29 +822,145 @@ impl FileSource {
         path: &ResourceId,
     ) -> Option<bool> {
         let locale = locale.borrow();
-        if !self.locales.contains(locale) {
-            Some(false)
-        } else {
-            let full_path = self.get_path(locale, path);
-            if let Some(index) = &self.index {
-                return Some(index.iter().any(|p| p == &full_path));
+        let locale_idx = match self.locale_idx(locale) {
+            Some(locale_idx) => locale_idx,
+            None => return Some(false),
+        };
+        if let Some(index) = &self.index {
+            return Some(index.contains(locale_idx, &path.value));
+        }
+        self.shared.has_file(&self.get_path(locale, path))
+    }
+
+    /// Returns `false` if the resource is known not to exist without looking
+    /// at the cache, i.e. the locale isn't served by this source or the index
+    /// doesn't list the resource. Cached misses and failures are left to the
+    /// cache lookup, so that they are reported and counted as cache hits.
+    fn may_have_file(&self, locale: &LanguageIdentifier, path: &ResourceId) -> bool {
+        match self.locale_idx(locale) {
+            Some(locale_idx) => self
+                .index
+                .as_ref()
+                .is_none_or(|index| index.contains(locale_idx, &path.value)),
+            None => false,
+        }
+    }
+
+    /// Drop the cached resource for the combination of `locale` and `path`,
+    /// so that the next request fetches it again.
+    ///
+    /// A pending async load of the resource still resolves, but its result
+    /// is not stored in the cache. The index, if any, is not affected.
+    pub fn invalidate(&self, locale: &LanguageIdentifier, path: &ResourceId) {
+        self.invalidate_path(&self.get_path(locale, path));
+    }
+
+    /// Drop the cached resource stored under `full_path`, i.e. the path
+    /// passed to the [`FileFetcher`](trait.FileFetcher.html).
+    /// See [`invalidate`](#method.invalidate).
+    pub fn invalidate_path(&self, full_path: &str) {
+        self.shared.entries.borrow_mut().remove(full_path);
+    }
+
+    /// Drop all cached resources for `locale`.
+    /// See [`invalidate`](#method.invalidate).
+    pub fn invalidate_locale(&self, locale: &LanguageIdentifier) {
+        let locale = self.resolve_locale(locale).unwrap_or(locale);
+        self.shared.entries.borrow_mut().remove_locale(locale);
+    }
+
+    /// Drop all cached resources.
+    /// See [`invalidate`](#method.invalidate).
+    pub fn clear_cache(&self) {
+        self.shared.entries.borrow_mut().clear();
+    }
+
+    /// Returns the total length of the sources of all resources currently
+    /// held in the cache.
+    pub fn cached_bytes(&self) -> usize {
+        self.shared.entries.borrow().loaded_bytes()
+    }
+
+    /// Add the resources currently loaded by the source to `cache`, along
+    /// with the stamps they had when they were fetched if the source's
+    /// options enable `record_stamps`.
+    pub fn save_startup_cache(&self, cache: &mut StartupCache) {
+        let entries = self.shared.entries.borrow();
+        for (full_path, locale, res, stamp) in entries.loaded() {
+            cache.insert(
+                self.name.clone(),
+                full_path.to_string(),
+                StartupCacheEntry {
+                    locale: locale.clone(),
+                    stamp: stamp.map(str::to_string),
+                    text: res.source().to_string(),
+                },
+            );
+        }
+    }
+
+    /// Parse the resources saved in `cache` for this source into its cache,
+    /// skipping the ones whose stamp has changed since, or which are already
+    /// cached. Returns the number of resources added.
+    pub fn seed_startup_cache(&self, cache: &StartupCache) -> usize {
+        let mut seeded = 0;
+        for (full_path, entry) in cache.source_entries(&self.name) {
+            if !self.locales.contains(&entry.locale) {
+                continue;
+            }
+            let full_path_id: ResourceId = full_path.into();
+            if self.shared.fetcher.stamp(&full_path_id) != entry.stamp {
+                continue;
+            }
+            if self.shared.entries.borrow().get(full_path).is_some() {
+                continue;
+            }
+            let source = entry.text.clone().into_bytes();
+            #[cfg(feature = "checksums")]
+            let source = self.shared.restore_bom(full_path, source);
+            if let ResourceOption::Some(res) =
+                self.shared
+                    .parse_resource(&entry.locale, &full_path_id, Ok(source))
+            {
+                let mut entries = self.shared.entries.borrow_mut();
+                let mut entry_id = 0;
+                entries.lookup(full_path.to_string(), &entry.locale, |id| {
+                    entry_id = id;
+                    ResourceStatus::Loaded(res)
+                });
+                entries.set_stamp(full_path, entry_id, entry.stamp.clone());
+                seeded += 1;
             }
-            self.shared.has_file(&full_path)
         }
+        seeded
+    }
+
+    /// Returns a snapshot of the counters of the source. They are shared by
+    /// all clones of the source, and not reset by invalidating its cache.
+    pub fn stats(&self) -> SourceStats {
+        self.shared.stats.snapshot()
     }
 
     pub fn locales(&self) -> &[LanguageIdentifier] {
         &self.locales
     }
 
+    pub fn pre_path(&self) -> &str {
+        &self.pre_path
+    }
+
+    pub fn options(&self) -> &FileSourceOptions {
+        &self.options
+    }
+
     pub fn get_index(&self) -> Option<&Vec<String>> {
-        self.index.as_ref()
+        self.index.as_ref().map(|index| index.paths())
     }
 }
 
 impl std::fmt::Debug for FileSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(index) = &self.index {
+        if let Some(index) = self.get_index() {
             f.debug_struct("FileSource")
                 .field("name", &self.name)
                 .field("metasource", &self.metasource)
@@ -404,61 +980,262 @@ impl std::fmt::Debug for FileSource {
 }
 
 impl Inner {
-    fn lookup_resource<F>(&self, resource_id: ResourceId, f: F) -> ResourceStatus
+    /// Return the cached status of `resource_id`, calling `f` with the id of
+    /// the new entry to create it, along with the stamp of the resource if
+    /// it has been fetched, if there is none.
+    fn lookup_resource<F>(
+        &self,
+        resource_id: ResourceId,
+        locale: &LanguageIdentifier,
+        f: F,
+    ) -> ResourceStatus
     where
-        F: FnOnce() -> ResourceStatus,
+        F: FnOnce(u64) -> (ResourceStatus, Option<String>),
     {
-        let mut lock = self.entries.borrow_mut();
-        lock.entry(resource_id.value).or_insert_with(|| f()).clone()
+        let mut created = None;
+        let status = {
+            let mut entries = self.entries.borrow_mut();
+            let status = entries.lookup(resource_id.value.clone(), locale, |entry_id| {
+                let (status, stamp) = f(entry_id);
+                created = Some((entry_id, stamp));
+                status
+            });
+            if let Some((entry_id, stamp)) = &mut created {
+                entries.set_stamp(&resource_id.value, *entry_id, stamp.take());
+            }
+            status
+        };
+        let miss = created.is_some();
+        self.stats.update(|stats| {
+            if miss {
+                stats.cache_misses += 1;
+            } else {
+                stats.cache_hits += 1;
+            }
+        });
+        status
     }
 
-    fn update_resource(&self, resource_id: ResourceId, resource: ResourceOption) -> ResourceOption {
+    /// Store the result of an async load in the cache entry it was started
+    /// for. If the entry has been invalidated in the meantime, the result is
+    /// only delivered to the pending futures.
+    ///
+    /// A timeout may be transient, so instead of caching it the entry is
+    /// removed, and the next request fetches the resource again.
+    fn update_resource(
+        &self,
+        resource_id: ResourceId,
+        entry_id: u64,
+        fetched: Fetched,
+    ) -> ResourceOption {
+        let resource = fetched.resource;
         let mut lock = self.entries.borrow_mut();
-        let entry = lock.get_mut(&resource_id.value);
-        match entry {
-            Some(entry) => *entry = resource.clone().into(),
-            _ => panic!("Expected "),
+        match resource {
+            ResourceOption::FetchFailed {
+                kind: io::ErrorKind::TimedOut,
+                ..
+            } => {
+                if lock.loading_id(&resource_id.value) == Some(entry_id) {
+                    lock.remove(&resource_id.value);
+                }
+            }
+            _ => {
+                lock.update(&resource_id.value, entry_id, resource.clone().into());
+                lock.set_stamp(&resource_id.value, entry_id, fetched.stamp);
+            }
         }
         resource
     }
 
+    fn report_errors(&self, errors: Vec<L10nRegistryError>) {
+        if let Some(reporter) = &self.error_reporter {
+            reporter.borrow().report_errors(errors);
+        }
+    }
+
+    /// Parse the result of fetching `resource_id`, reporting any errors.
+    /// See [`parse_fetched`].
+    fn parse_resource(
+        &self,
+        locale: &LanguageIdentifier,
+        resource_id: &ResourceId,
+        source: io::Result<Vec<u8>>,
+    ) -> ResourceOption {
+        let parsed = parse_fetched(
+            locale,
+            resource_id,
+            source,
+            &self.stats,
+            self.checksums.as_deref(),
+            &|| self.fetcher.origin(resource_id),
+            &|errors| self.report_errors(errors),
+        );
+        match parsed {
+            Parsed::Missing => ResourceOption::missing_resource(resource_id),
+            Parsed::Failed(kind) => ResourceOption::fetch_failed(resource_id, kind),
+            Parsed::Resource(res) => ResourceOption::Some(Rc::new(res)),
+        }
+    }
+
+    /// Prepend the BOM removed from the text of a resource saved in a
+    /// startup cache, if that's what the checksum manifest was computed on.
+    #[cfg(feature = "checksums")]
+    fn restore_bom(&self, full_path: &str, source: Vec<u8>) -> Vec<u8> {
+        match &self.checksums {
+            Some(checksums) if !checksums.matches(full_path, &source) => {
+                let with_bom = [UTF8_BOM, &source].concat();
+                if checksums.matches(full_path, &with_bom) {
+                    with_bom
+                } else {
+                    source
+                }
+            }
+            _ => source,
+        }
+    }
+
+    /// Complete the pending async load of `full_path`, if any, with the
+    /// result of a sync load.
+    fn resolve_pending(&self, full_path: &str, fetched: Fetched) {
+        let entry_id = {
+            let mut lock = self.entries.borrow_mut();
+            match lock.loading_id(full_path) {
+                Some(entry_id) => {
+                    lock.update(full_path, entry_id, fetched.resource.clone().into());
+                    lock.set_stamp(full_path, entry_id, fetched.stamp);
+                    entry_id
+                }
+                None => return,
+            }
+        };
+        if let Some(sender) = self.pending.borrow_mut().remove(&entry_id) {
+            let _ = sender.send(fetched.resource);
+        }
+    }
+
+    /// Returns the stamp to record for a resource about to be fetched.
+    fn stamp(&self, resource_id: &ResourceId) -> Option<String> {
+        if self.record_stamps {
+            self.fetcher.stamp(resource_id)
+        } else {
+            None
+        }
+    }
+
     pub fn has_file(&self, full_path: &str) -> Option<bool> {
         match self.entries.borrow().get(full_path) {
             Some(ResourceStatus::MissingRequired) => Some(false),
             Some(ResourceStatus::MissingOptional) => Some(false),
+            Some(ResourceStatus::FetchFailed { .. }) => Some(false),
             Some(ResourceStatus::Loaded(_)) => Some(true),
             Some(ResourceStatus::Loading(_)) | None => None,
         }
     }
 }
 
-async fn read_resource(resource_id: ResourceId, shared: Rc<Inner>) -> ResourceOption {
-    let resource = shared
-        .fetcher
-        .fetch(&resource_id)
-        .await
-        .ok()
-        .map(|source| match FluentResource::try_new(source) {
-            Ok(res) => ResourceOption::Some(Rc::new(res)),
-            Err((res, errors)) => {
-                if let Some(reporter) = &shared.error_reporter.borrow() {
-                    reporter.borrow().report_errors(
-                        errors
-                            .into_iter()
-                            .map(|e| L10nRegistryError::FluentError {
-                                resource_id: resource_id.clone(),
-                                loc: Some(calculate_pos_in_source(res.source(), e.pos.start)),
-                                error: e.into(),
-                            })
-                            .collect(),
-                    );
-                }
-                ResourceOption::Some(Rc::new(res))
+/// The result of loading a resource, along with the
+/// [`FileFetcher::stamp`](trait.FileFetcher.html#method.stamp) it had before
+/// it was fetched, so that a change during the fetch makes the stamp stale.
+#[derive(Clone)]
+struct Fetched {
+    resource: ResourceOption,
+    stamp: Option<String>,
+}
+
+/// Cleans up after the load of a cache entry when it is dropped, removing
+/// the entry if the load didn't complete.
+struct LoadGuard {
+    resource_id: ResourceId,
+    entry_id: u64,
+    shared: Rc<Inner>,
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.shared.pending.try_borrow_mut() {
+            pending.remove(&self.entry_id);
+        }
+        // If the cache is borrowed, the abandoned entry is replaced on its
+        // next lookup instead.
+        if let Ok(mut entries) = self.shared.entries.try_borrow_mut() {
+            if entries.loading_id(&self.resource_id.value) == Some(self.entry_id) {
+                entries.remove(&self.resource_id.value);
             }
+        }
+    }
+}
+
+/// Resolve with the result of a sync load sent through `sync_result`, if
+/// there is one, and otherwise with the result of `read`, storing it in the
+/// cache entry of `guard`.
+async fn load_resource(
+    guard: LoadGuard,
+    sync_result: oneshot::Receiver<ResourceOption>,
+    read: impl Future<Output = Fetched>,
+) -> ResourceOption {
+    futures::pin_mut!(read);
+    // `select` polls the receiver first, so a sync load completed before this
+    // future is first polled prevents the async fetch from starting at all.
+    match future::select(sync_result, read).await {
+        // The sync load has already updated the cache.
+        Either::Left((Ok(resource), _)) => resource,
+        Either::Left((Err(oneshot::Canceled), read)) => {
+            let fetched = read.await;
+            guard
+                .shared
+                .update_resource(guard.resource_id.clone(), guard.entry_id, fetched)
+        }
+        Either::Right((fetched, _)) => {
+            guard
+                .shared
+                .update_resource(guard.resource_id.clone(), guard.entry_id, fetched)
+        }
+    }
+}
+
+async fn read_resource(
+    resource_id: ResourceId,
+    locale: LanguageIdentifier,
+    shared: Rc<Inner>,
+) -> Fetched {
+    let stamp = shared.stamp(&resource_id);
+    let start = Instant::now();
+    let source = shared
+        .fetch_policy
+        .fetch_bytes(shared.fetcher.as_ref(), &resource_id)
+        .await;
+    shared.stats.record_fetch(1, start);
+    Fetched {
+        resource: shared.parse_resource(&locale, &resource_id, source),
+        stamp,
+    }
+}
+
+async fn read_resources(
+    resource_ids: Vec<ResourceId>,
+    locale: LanguageIdentifier,
+    shared: Rc<Inner>,
+) -> Rc<Vec<Fetched>> {
+    let stamps: Vec<Option<String>> = resource_ids
+        .iter()
+        .map(|resource_id| shared.stamp(resource_id))
+        .collect();
+    let start = Instant::now();
+    let sources = shared
+        .fetch_policy
+        .fetch_many(shared.fetcher.as_ref(), &resource_ids)
+        .await;
+    shared.stats.record_fetch(resource_ids.len() as u64, start);
+    let resources = resource_ids
+        .iter()
+        .zip(sources)
+        .zip(stamps)
+        .map(|((resource_id, source), stamp)| Fetched {
+            resource: shared.parse_resource(&locale, resource_id, source),
+            stamp,
         })
-        .unwrap_or_else(|| ResourceOption::missing_resource(&resource_id));
-    // insert the resource into the cache
-    shared.update_resource(resource_id, resource)
+        .collect();
+    Rc::new(resources)
 }
 
 #[cfg(test)]
@@ -551,7 +1328,7 @@ mod tests_tokio {
         let fs1 =
             fetcher.get_test_file_source("toolkit", None, vec![en_us.clone()], "toolkit/{locale}/");
 
-        let _ = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into());
+        drop(fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into()));
         let file2 = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), true);
         assert!(file2.is_some());
     }
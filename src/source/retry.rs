@@ -0,0 +1,136 @@
+use super::FileFetcher;
+use crate::env::Timer;
+use fluent_fallback::types::ResourceId;
+use futures::future::{self, Either};
+use std::{future::Future, io, time::Duration};
+
+/// How a [`FileSource`] retries async fetches which fail with an error other
+/// than [`io::ErrorKind::NotFound`], including timeouts.
+///
+/// The delay before the first retry is `backoff`, and it doubles with every
+/// following one. Delays require a timer, see
+/// [`FileSource::set_timer`](struct.FileSource.html#method.set_timer);
+/// without one, fetches are retried right away.
+///
+/// [`FileSource`]: struct.FileSource.html
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+/// The timeout and retry settings of a `FileSource`, applied around the
+/// async methods of its fetcher.
+pub(super) struct FetchPolicy {
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    pub timer: Option<Box<dyn Timer>>,
+}
+
+fn is_retryable(err: &io::Error) -> bool {
+    err.kind() != io::ErrorKind::NotFound
+}
+
+fn timed_out(timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("Fetch timed out after {:?}", timeout),
+    )
+}
+
+impl FetchPolicy {
+    /// Resolve with the output of `fetch`, or with the timeout if it elapses
+    /// first.
+    async fn timeout<T>(&self, fetch: impl Future<Output = T>) -> Result<T, Duration> {
+        let (timeout, timer) = match (self.timeout, &self.timer) {
+            (Some(timeout), Some(timer)) => (timeout, timer),
+            _ => return Ok(fetch.await),
+        };
+        futures::pin_mut!(fetch);
+        match future::select(fetch, timer.sleep(timeout)).await {
+            Either::Left((result, _)) => Ok(result),
+            Either::Right(_) => Err(timeout),
+        }
+    }
+
+    async fn backoff(&self, attempt: u32) {
+        let delay = self
+            .retry
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt));
+        if let Some(timer) = &self.timer {
+            if !delay.is_zero() {
+                timer.sleep(delay).await;
+            }
+        }
+    }
+
+    pub async fn fetch_bytes(
+        &self,
+        fetcher: &dyn FileFetcher,
+        resource_id: &ResourceId,
+    ) -> io::Result<Vec<u8>> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .timeout(fetcher.fetch_bytes(resource_id))
+                .await
+                .unwrap_or_else(|timeout| Err(timed_out(timeout)));
+            match result {
+                Err(err) if attempt < self.retry.max_retries && is_retryable(&err) => {
+                    self.backoff(attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Fetch `resource_ids` in batches, retrying only the ones which failed.
+    /// Returns exactly one result per resource.
+    pub async fn fetch_many(
+        &self,
+        fetcher: &dyn FileFetcher,
+        resource_ids: &[ResourceId],
+    ) -> Vec<io::Result<Vec<u8>>> {
+        let mut results: Vec<Option<io::Result<Vec<u8>>>> =
+            resource_ids.iter().map(|_| None).collect();
+        let mut remaining: Vec<usize> = (0..resource_ids.len()).collect();
+        let mut attempt = 0;
+        loop {
+            let batch_ids: Vec<ResourceId> = remaining
+                .iter()
+                .map(|&idx| resource_ids[idx].clone())
+                .collect();
+            let batch = match self.timeout(fetcher.fetch_many(&batch_ids)).await {
+                Ok(batch) => batch,
+                Err(timeout) => batch_ids.iter().map(|_| Err(timed_out(timeout))).collect(),
+            };
+            let can_retry = attempt < self.retry.max_retries;
+            let mut failed = vec![];
+            for (idx, result) in remaining.into_iter().zip(batch) {
+                match result {
+                    Err(err) if can_retry && is_retryable(&err) => failed.push(idx),
+                    result => results[idx] = Some(result),
+                }
+            }
+            if failed.is_empty() {
+                break;
+            }
+            self.backoff(attempt).await;
+            attempt += 1;
+            remaining = failed;
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| {
+                    Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Missing result of a batched fetch",
+                    ))
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use unic_langid::LanguageIdentifier;
+
+const MAGIC: &[u8] = b"L10NSC01";
+
+/// The sources of the resources loaded during a session, saved to seed the
+/// caches of the [`FileSource`]s on the next start instead of fetching the
+/// resources again.
+///
+/// Entries are keyed by the name of their source and their full path. Each
+/// one carries the [`FileFetcher::stamp`] of its resource at the time it was
+/// fetched, and is only used if the stamp is still the same. Stamps are only
+/// read for sources whose options enable [`record_stamps`]. The `version` of
+/// the whole cache, e.g. a build id, guards the entries of fetchers which
+/// don't provide stamps.
+///
+/// [`FileSource`]: struct.FileSource.html
+/// [`record_stamps`]: struct.FileSourceOptions.html#structfield.record_stamps
+/// [`FileFetcher::stamp`]: trait.FileFetcher.html#method.stamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupCache {
+    version: String,
+    /// Sorted by source and path, so that a source's entries are adjacent
+    /// and the same cache always writes the same blob.
+    entries: BTreeMap<(String, String), StartupCacheEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct StartupCacheEntry {
+    pub locale: LanguageIdentifier,
+    pub stamp: Option<String>,
+    pub text: String,
+}
+
+impl StartupCache {
+    pub fn new<S: Into<String>>(version: S) -> Self {
+        Self {
+            version: version.into(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add an entry, replacing the one of the same source and path.
+    pub(super) fn insert(&mut self, source: String, full_path: String, entry: StartupCacheEntry) {
+        self.entries.insert((source, full_path), entry);
+    }
+
+    /// Returns the full path and entry of each resource of `source`.
+    pub(super) fn source_entries<'c>(
+        &'c self,
+        source: &'c str,
+    ) -> impl Iterator<Item = (&'c str, &'c StartupCacheEntry)> {
+        self.entries
+            .range((source.to_string(), String::new())..)
+            .take_while(move |((s, _), _)| s == source)
+            .map(|((_, full_path), entry)| (full_path.as_str(), entry))
+    }
+
+    /// Read a cache written by [`write`](#method.write). A cache written
+    /// with a different `version` is stale, and an empty cache is returned
+    /// instead.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the data is not a cache.
+    pub fn read<R: Read>(mut reader: R, version: &str) -> io::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid_data("Not a startup cache"));
+        }
+        let mut cache = Self::new(version);
+        if read_string(&mut reader)? != version {
+            return Ok(cache);
+        }
+        for _ in 0..read_u32(&mut reader)? {
+            let source = read_string(&mut reader)?;
+            let full_path = read_string(&mut reader)?;
+            let locale = read_string(&mut reader)?
+                .parse()
+                .map_err(|_| invalid_data("Invalid locale in startup cache"))?;
+            let stamp = match read_u32(&mut reader)? {
+                0 => None,
+                _ => Some(read_string(&mut reader)?),
+            };
+            let text = read_string(&mut reader)?;
+            cache.insert(
+                source,
+                full_path,
+                StartupCacheEntry {
+                    locale,
+                    stamp,
+                    text,
+                },
+            );
+        }
+        Ok(cache)
+    }
+
+    /// Write the cache as a single blob, with its entries sorted so that the
+    /// same cache always produces the same blob.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_string(&mut writer, &self.version)?;
+        write_u32(&mut writer, self.entries.len())?;
+        for ((source, full_path), entry) in &self.entries {
+            write_string(&mut writer, source)?;
+            write_string(&mut writer, full_path)?;
+            write_string(&mut writer, &entry.locale.to_string())?;
+            match &entry.stamp {
+                Some(stamp) => {
+                    write_u32(&mut writer, 1)?;
+                    write_string(&mut writer, stamp)?;
+                }
+                None => write_u32(&mut writer, 0)?,
+            }
+            write_string(&mut writer, &entry.text)?;
+        }
+        writer.flush()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_u32(reader)? as usize;
+    let mut bytes = vec![];
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("Invalid UTF-8 in startup cache"))
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| invalid_data("Startup cache entry too large"))?;
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len())?;
+    writer.write_all(value.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unic_langid::langid;
+
+    #[test]
+    fn roundtrip() {
+        let mut cache = StartupCache::new("build-1");
+        let entry = |stamp: Option<&str>| StartupCacheEntry {
+            locale: langid!("pl"),
+            stamp: stamp.map(str::to_string),
+            text: "menu-file = Plik\n".to_string(),
+        };
+        cache.insert(
+            "toolkit".to_string(),
+            "toolkit/pl/menu.ftl".to_string(),
+            entry(Some("17:1")),
+        );
+        cache.insert(
+            "browser".to_string(),
+            "browser/pl/menu.ftl".to_string(),
+            entry(None),
+        );
+        cache.insert(
+            "browser".to_string(),
+            "browser/pl/menu.ftl".to_string(),
+            entry(Some("17:2")),
+        );
+
+        let mut blob = vec![];
+        cache.write(&mut blob).unwrap();
+        let read = StartupCache::read(blob.as_slice(), "build-1").unwrap();
+        assert_eq!(read, cache);
+        assert_eq!(read.len(), 2);
+        assert_eq!(
+            read.source_entries("browser").collect::<Vec<_>>(),
+            vec![("browser/pl/menu.ftl", &entry(Some("17:2")))]
+        );
+        assert_eq!(read.source_entries("app").count(), 0);
+
+        let stale = StartupCache::read(blob.as_slice(), "build-2").unwrap();
+        assert!(stale.is_empty());
+        assert_eq!(stale.version(), "build-2");
+
+        let err = StartupCache::read(&blob[..blob.len() - 1], "build-1").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err = StartupCache::read(&b"menu-file = Plik\n"[..], "build-1").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
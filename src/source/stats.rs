@@ -0,0 +1,99 @@
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A snapshot of the counters of a [`FileSource`], see
+/// [`FileSource::stats`]. Snapshots of several sources can be summed up.
+///
+/// [`FileSource`]: struct.FileSource.html
+/// [`FileSource::stats`]: struct.FileSource.html#method.stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceStats {
+    /// Lookups answered by the cache, including cached misses and failures,
+    /// and the ones which wait for a load already in progress. Requests for
+    /// a locale the source doesn't serve, or for a resource missing from its
+    /// index, are answered without a lookup and aren't counted.
+    pub cache_hits: u64,
+    /// Lookups which started a new load.
+    pub cache_misses: u64,
+    /// Resources requested from the fetcher, counting each resource of a
+    /// batch separately. Retries aren't counted again.
+    pub fetches: u64,
+    /// Errors found while decoding and parsing the fetched resources.
+    pub parse_errors: u64,
+    /// The total size of the fetched resources.
+    pub bytes_loaded: u64,
+    /// The cumulative time spent fetching, including the waits of the retry
+    /// policy. Concurrent fetches each count in full.
+    pub fetch_time: Duration,
+    /// The cumulative time spent decoding and parsing.
+    pub parse_time: Duration,
+}
+
+impl AddAssign for SourceStats {
+    fn add_assign(&mut self, other: Self) {
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.fetches += other.fetches;
+        self.parse_errors += other.parse_errors;
+        self.bytes_loaded += other.bytes_loaded;
+        self.fetch_time += other.fetch_time;
+        self.parse_time += other.parse_time;
+    }
+}
+
+impl Add for SourceStats {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl Sum for SourceStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+/// The live counters of a source, shared with the thread-safe sources.
+#[derive(Default)]
+pub(crate) struct StatsCounter {
+    stats: Mutex<SourceStats>,
+}
+
+impl StatsCounter {
+    pub fn snapshot(&self) -> SourceStats {
+        *self.lock()
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut SourceStats)) {
+        f(&mut self.lock());
+    }
+
+    // The counters stay valid even if a thread panicked while updating them.
+    fn lock(&self) -> std::sync::MutexGuard<'_, SourceStats> {
+        self.stats
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run `f`, adding the time it takes to `fetch_time` along with the
+    /// `fetches` made.
+    pub fn time_fetch<T>(&self, fetches: u64, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record_fetch(fetches, start);
+        result
+    }
+
+    pub fn record_fetch(&self, fetches: u64, start: Instant) {
+        let elapsed = start.elapsed();
+        self.update(|stats| {
+            stats.fetches += fetches;
+            stats.fetch_time += elapsed;
+        });
+    }
+}
@@ -0,0 +1,160 @@
+use crate::errors::L10nRegistrySetupError;
+use std::collections::HashMap;
+use unic_langid::LanguageIdentifier;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Locale,
+    Language,
+    Script,
+    Region,
+}
+
+/// A parsed `pre_path` of a `FileSource`.
+///
+/// The template may contain the following placeholders:
+///  * `{locale}` - the whole locale, e.g. `sr-Cyrl-RS`,
+///  * `{language}`, `{script}` and `{region}` - the subtags of the locale,
+///    expanded to an empty string when the locale doesn't have them, in
+///    which case a path segment left empty is removed,
+///  * any variable defined in
+///    [`FileSourceOptions::variables`](struct.FileSourceOptions.html#structfield.variables).
+///
+/// Variables are substituted when the template is parsed, the locale
+/// placeholders when it is expanded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathTemplate {
+    parts: Vec<Part>,
+}
+
+impl PathTemplate {
+    /// Parse `pre_path`, returning an error if it contains a placeholder which
+    /// is neither a locale subtag nor one of the `variables`.
+    pub fn new(
+        pre_path: &str,
+        variables: &HashMap<String, String>,
+    ) -> Result<Self, L10nRegistrySetupError> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut rest = pre_path;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            text.push_str(&rest[..start]);
+            let placeholder = &rest[start + 1..end];
+            let part = match placeholder {
+                "locale" => Part::Locale,
+                "language" => Part::Language,
+                "script" => Part::Script,
+                "region" => Part::Region,
+                _ => match variables.get(placeholder) {
+                    Some(value) => {
+                        text.push_str(value);
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                    None => {
+                        return Err(L10nRegistrySetupError::UnknownPlaceholder {
+                            pre_path: pre_path.to_string(),
+                            placeholder: placeholder.to_string(),
+                        })
+                    }
+                },
+            };
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Expand the template for `locale`.
+    ///
+    /// A path segment made only of subtags the locale doesn't have is left
+    /// out along with its `/`, so that `{language}/{script}/` expands to `pl/`
+    /// rather than `pl//`.
+    pub fn expand(&self, locale: &LanguageIdentifier) -> String {
+        let mut result = String::new();
+        // Whether the current segment only consists of empty subtags so far.
+        let mut empty_segment = false;
+        for part in &self.parts {
+            let value = match part {
+                Part::Text(text) => {
+                    let text = match text.strip_prefix('/') {
+                        Some(rest) if empty_segment => rest,
+                        _ => text,
+                    };
+                    result.push_str(text);
+                    empty_segment = false;
+                    continue;
+                }
+                Part::Locale => Some(locale.to_string()),
+                Part::Language => Some(locale.language.as_str().to_string()),
+                Part::Script => locale.script.map(|script| script.as_str().to_string()),
+                Part::Region => locale.region.map(|region| region.as_str().to_string()),
+            };
+            match value {
+                Some(value) => {
+                    result.push_str(&value);
+                    empty_segment = false;
+                }
+                None => {
+                    if result.is_empty() || result.ends_with('/') {
+                        empty_segment = true;
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let mut variables = HashMap::new();
+        variables.insert("platform".to_string(), "linux".to_string());
+
+        let sr: LanguageIdentifier = "sr-Cyrl-RS".parse().unwrap();
+        let pl: LanguageIdentifier = "pl".parse().unwrap();
+
+        let template = PathTemplate::new("/{platform}/{locale}/", &variables).unwrap();
+        assert_eq!(template.expand(&sr), "/linux/sr-Cyrl-RS/");
+
+        let template = PathTemplate::new("{language}/{script}/{region}/", &variables).unwrap();
+        assert_eq!(template.expand(&sr), "sr/Cyrl/RS/");
+        assert_eq!(template.expand(&pl), "pl/");
+        let sr_rs: LanguageIdentifier = "sr-RS".parse().unwrap();
+        assert_eq!(template.expand(&sr_rs), "sr/RS/");
+
+        let template = PathTemplate::new("/{script}/{language}-{region}/", &variables).unwrap();
+        assert_eq!(template.expand(&sr), "/Cyrl/sr-RS/");
+        assert_eq!(template.expand(&pl), "/pl-/");
+
+        let template = PathTemplate::new("data/{locale/", &variables).unwrap();
+        assert_eq!(template.expand(&pl), "data/{locale/");
+    }
+
+    #[test]
+    fn test_unknown_placeholder() {
+        assert_eq!(
+            PathTemplate::new("/{channel}/{locale}/", &HashMap::new()),
+            Err(L10nRegistrySetupError::UnknownPlaceholder {
+                pre_path: "/{channel}/{locale}/".to_string(),
+                placeholder: "channel".to_string(),
+            })
+        );
+    }
+}
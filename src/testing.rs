@@ -129,6 +129,12 @@ pub struct TestFileFetcher {
     inner: Rc<InnerFileFetcher>,
 }
 
+impl Default for TestFileFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestFileFetcher {
     pub fn new() -> Self {
         Self {
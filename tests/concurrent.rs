@@ -0,0 +1,519 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use fluent_bundle::FluentArgs;
+use futures::StreamExt;
+use l10nregistry::concurrent::{
+    BundleAdapter, FileFetcher, FileSource, FluentBundle, L10nRegistry, ResourceFuture,
+    ResourceOption,
+};
+use l10nregistry::env::ErrorReporter;
+use l10nregistry::errors::{L10nRegistryError, L10nRegistrySetupError};
+use l10nregistry::fetchers::DirectoryFileFetcher;
+use l10nregistry::source::{FileSourceOptions, ResourceId, RetryPolicy};
+use unic_langid::{langid, LanguageIdentifier};
+
+static FTL_RESOURCE: &str = "toolkit/menu.ftl";
+
+#[derive(Clone)]
+struct SharedFileFetcher {
+    files: Arc<HashMap<String, Result<Vec<u8>, io::ErrorKind>>>,
+    fetches: Arc<AtomicUsize>,
+}
+
+impl SharedFileFetcher {
+    fn new(files: &[(&str, &str)]) -> Self {
+        Self::with_results(
+            files
+                .iter()
+                .map(|(path, source)| (*path, Ok(source.as_bytes().to_vec()))),
+        )
+    }
+
+    /// A fetcher returning raw data or failing with an error for each path.
+    fn with_results<'a>(
+        files: impl IntoIterator<Item = (&'a str, Result<Vec<u8>, io::ErrorKind>)>,
+    ) -> Self {
+        Self {
+            files: Arc::new(
+                files
+                    .into_iter()
+                    .map(|(path, result)| (path.to_string(), result))
+                    .collect(),
+            ),
+            fetches: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl FileFetcher for SharedFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        String::from_utf8(self.fetch_bytes_sync(resource_id)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.fetch_sync(resource_id)
+    }
+
+    fn fetch_bytes_sync(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetches.fetch_add(1, Ordering::SeqCst);
+        match self.files.get(&resource_id.value) {
+            Some(Ok(source)) => Ok(source.clone()),
+            Some(Err(kind)) => Err(io::Error::new(*kind, "Fetch failed")),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "File not found")),
+        }
+    }
+
+    async fn fetch_bytes(&self, resource_id: &ResourceId) -> io::Result<Vec<u8>> {
+        self.fetch_bytes_sync(resource_id)
+    }
+}
+
+#[derive(Clone, Default)]
+struct SharedErrorReporter {
+    errors: Arc<Mutex<Vec<L10nRegistryError>>>,
+}
+
+impl ErrorReporter for SharedErrorReporter {
+    fn report_errors(&self, errors: Vec<L10nRegistryError>) {
+        self.errors.lock().unwrap().extend(errors);
+    }
+}
+
+struct NoopBundleAdapter;
+
+impl BundleAdapter for NoopBundleAdapter {
+    fn adapt_bundle(&self, _bundle: &mut FluentBundle) {}
+}
+
+fn get_fetcher() -> SharedFileFetcher {
+    SharedFileFetcher::new(&[
+        ("toolkit/en-US/toolkit/menu.ftl", "menu-file = File\n"),
+        ("toolkit/pl/toolkit/menu.ftl", "menu-file = Plik\n"),
+        ("browser/pl/toolkit/menu.ftl", "menu-file = Plik!\n"),
+    ])
+}
+
+fn get_source(name: &str, fetcher: &SharedFileFetcher) -> FileSource {
+    FileSource::new(
+        name.to_string(),
+        None,
+        vec![langid!("en-US"), langid!("pl")],
+        format!("{}/{{locale}}/", name),
+        FileSourceOptions::default(),
+        fetcher.clone(),
+    )
+}
+
+fn get_registry(
+    fetcher: &SharedFileFetcher,
+    reporter: SharedErrorReporter,
+) -> L10nRegistry<SharedErrorReporter, NoopBundleAdapter> {
+    let reg = L10nRegistry::with_provider(reporter);
+    reg.register_sources(vec![get_source("toolkit", fetcher)])
+        .unwrap();
+    reg
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_send_sync() {
+    assert_send_sync::<L10nRegistry<SharedErrorReporter, NoopBundleAdapter>>();
+    assert_send_sync::<FileSource>();
+    assert_send_sync::<ResourceFuture>();
+}
+
+#[test]
+fn test_fetch_file_shared_between_threads() {
+    let fetcher = get_fetcher();
+    let source = get_source("toolkit", &fetcher);
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let source = source.clone();
+            thread::spawn(move || {
+                source
+                    .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+                    .is_some()
+            })
+        })
+        .collect();
+    for worker in workers {
+        assert!(worker.join().unwrap());
+    }
+
+    // Threads racing for the resource may each fetch it, but none of them
+    // fetches it again once it is cached.
+    let fetches = fetcher.fetches.load(Ordering::SeqCst);
+    assert!((1..=4).contains(&fetches));
+    assert!(source
+        .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+        .is_some());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), fetches);
+
+    let missing = source.fetch_file_sync(&langid!("de"), &FTL_RESOURCE.into(), true);
+    assert!(matches!(missing, ResourceOption::MissingRequired));
+}
+
+#[test]
+fn test_generate_bundles_sync_in_threads() {
+    let fetcher = get_fetcher();
+    let reg = get_registry(&fetcher, SharedErrorReporter::default());
+
+    let workers: Vec<_> = vec![langid!("pl"), langid!("en-US")]
+        .into_iter()
+        .map(|locale| {
+            let reg = reg.clone();
+            thread::spawn(move || {
+                let mut bundles =
+                    reg.generate_bundles_sync(vec![locale].into_iter(), vec![FTL_RESOURCE.into()]);
+                let bundle = bundles.next().unwrap().ok().unwrap();
+                assert!(bundles.next().is_none());
+                let msg = bundle.get_message("menu-file").unwrap();
+                let mut errors = vec![];
+                bundle
+                    .format_pattern(msg.value().unwrap(), None::<&FluentArgs>, &mut errors)
+                    .to_string()
+            })
+        })
+        .collect();
+    let values: Vec<String> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(values, vec!["Plik", "File"]);
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 2);
+
+    // Bundles generated later reuse the resources parsed by the workers.
+    let mut bundles = reg.generate_bundles_sync(
+        vec![langid!("pl"), langid!("en-US")].into_iter(),
+        vec![FTL_RESOURCE.into()],
+    );
+    assert!(bundles.next().is_some());
+    assert!(bundles.next().is_some());
+    assert!(bundles.next().is_none());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_bundles_spawned() {
+    let fetcher = get_fetcher();
+    let reg = get_registry(&fetcher, SharedErrorReporter::default());
+    reg.register_sources(vec![get_source("browser", &fetcher)])
+        .unwrap();
+
+    let task_reg = reg.clone();
+    let locales: Vec<LanguageIdentifier> = tokio::spawn(async move {
+        let mut bundles = task_reg.generate_bundles(
+            vec![langid!("en-US"), langid!("pl")].into_iter(),
+            vec![FTL_RESOURCE.into()],
+        );
+        let mut locales = vec![];
+        while let Some(bundle) = bundles.next().await {
+            locales.push(bundle.ok().unwrap().locales[0].clone());
+        }
+        locales
+    })
+    .await
+    .unwrap();
+
+    // The browser source only has `pl`, and is tried before toolkit.
+    assert_eq!(
+        locales,
+        vec![langid!("en-US"), langid!("pl"), langid!("pl")]
+    );
+    assert_eq!(
+        reg.get_source_names().unwrap(),
+        vec!["toolkit".to_string(), "browser".to_string()]
+    );
+}
+
+#[test]
+fn test_missing_resource_reported() {
+    let fetcher = get_fetcher();
+    let reporter = SharedErrorReporter::default();
+    let reg = get_registry(&fetcher, reporter.clone());
+
+    let mut bundles =
+        reg.generate_bundles_sync(vec![langid!("pl")].into_iter(), vec!["missing.ftl".into()]);
+    assert!(bundles.next().is_none());
+
+    let errors = reporter.errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        &errors[0],
+        L10nRegistryError::MissingResource { locale, .. } if *locale == langid!("pl")
+    ));
+}
+
+#[tokio::test]
+async fn test_fetch_file_sync_resolves_pending_load() {
+    let fetcher = get_fetcher();
+    let source = get_source("toolkit", &fetcher);
+
+    let pending = source.fetch_file(&langid!("pl"), &FTL_RESOURCE.into());
+    let res: Option<_> = source
+        .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+        .into();
+
+    // The async load completes with the result of the sync load, without
+    // fetching the resource again.
+    let async_res: Option<_> = pending.await.into();
+    assert!(Arc::ptr_eq(&res.unwrap(), &async_res.unwrap()));
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+
+    let stats = source.stats();
+    assert_eq!(stats.cache_misses, 1);
+    assert_eq!(stats.cache_hits, 1);
+    assert_eq!(stats.fetches, 1);
+}
+
+#[test]
+fn test_abandoned_load_releases_source() {
+    let fetcher = get_fetcher();
+    let source = get_source("toolkit", &fetcher);
+
+    // Nobody waits for the load anymore, so it doesn't keep the source alive.
+    drop(source.fetch_file(&langid!("pl"), &FTL_RESOURCE.into()));
+    assert!(source
+        .has_file(&langid!("pl"), &FTL_RESOURCE.into())
+        .is_none());
+    assert!(source
+        .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+        .is_some());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+
+    let pending = source.fetch_file(&langid!("en-US"), &FTL_RESOURCE.into());
+    drop(source);
+    drop(pending);
+    assert_eq!(Arc::strong_count(&fetcher.files), 1);
+}
+
+#[test]
+fn test_new_with_index() {
+    let fetcher = get_fetcher();
+    let source = FileSource::new_with_index(
+        "toolkit".to_string(),
+        None,
+        vec![langid!("en-US"), langid!("pl")],
+        "toolkit/{locale}/".to_string(),
+        FileSourceOptions::default(),
+        fetcher.clone(),
+        vec!["toolkit/pl/toolkit/menu.ftl".to_string()],
+    );
+    assert_eq!(source.pre_path(), "toolkit/{locale}/");
+
+    assert_eq!(
+        source.has_file(&langid!("pl"), &FTL_RESOURCE.into()),
+        Some(true)
+    );
+    assert_eq!(
+        source.has_file(&langid!("en-US"), &FTL_RESOURCE.into()),
+        Some(false)
+    );
+
+    // Files missing from the index aren't fetched.
+    assert!(source
+        .fetch_file_sync(&langid!("en-US"), &FTL_RESOURCE.into(), true)
+        .is_required_and_missing());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_fetch_failed() {
+    let fetcher = SharedFileFetcher::with_results(vec![
+        (
+            "toolkit/pl/toolkit/menu.ftl",
+            Err(io::ErrorKind::PermissionDenied),
+        ),
+        ("toolkit/en-US/toolkit/menu.ftl", Ok(vec![b'a', 0xff])),
+    ]);
+    let reporter = SharedErrorReporter::default();
+    let mut source = get_source("toolkit", &fetcher);
+    source.set_reporter(reporter.clone());
+
+    let failed = source.fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true);
+    assert!(matches!(
+        failed,
+        ResourceOption::FetchFailed {
+            required: true,
+            kind: io::ErrorKind::PermissionDenied,
+        }
+    ));
+    assert!(failed.is_required_and_missing());
+
+    // The failure is cached like a missing resource.
+    assert_eq!(
+        source.has_file(&langid!("pl"), &FTL_RESOURCE.into()),
+        Some(false)
+    );
+    assert!(source
+        .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+        .is_fetch_failed());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+
+    let invalid = source.fetch_file_sync(&langid!("en-US"), &FTL_RESOURCE.into(), true);
+    assert!(matches!(
+        invalid,
+        ResourceOption::FetchFailed {
+            kind: io::ErrorKind::InvalidData,
+            ..
+        }
+    ));
+
+    let errors = reporter.errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        &errors[0],
+        L10nRegistryError::FetchError {
+            kind: io::ErrorKind::PermissionDenied,
+            ..
+        }
+    ));
+    assert!(matches!(
+        &errors[1],
+        L10nRegistryError::InvalidEncoding { valid_up_to: 1, .. }
+    ));
+}
+
+#[test]
+fn test_unsupported_options() {
+    let fetcher = get_fetcher();
+    let try_new = |options: FileSourceOptions| {
+        FileSource::try_new(
+            "toolkit".to_string(),
+            None,
+            vec![langid!("pl")],
+            "toolkit/{locale}/".to_string(),
+            options,
+            fetcher.clone(),
+        )
+    };
+
+    let unsupported = vec![
+        (
+            "fetch_timeout",
+            FileSourceOptions {
+                fetch_timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+        ),
+        (
+            "retry_policy",
+            FileSourceOptions {
+                retry_policy: RetryPolicy {
+                    max_retries: 2,
+                    backoff: Duration::ZERO,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "record_stamps",
+            FileSourceOptions {
+                record_stamps: true,
+                ..Default::default()
+            },
+        ),
+    ];
+    for (name, options) in unsupported {
+        assert!(matches!(
+            try_new(options),
+            Err(L10nRegistrySetupError::UnsupportedOption { option, .. }) if option == name
+        ));
+    }
+    assert!(try_new(FileSourceOptions::default()).is_ok());
+}
+
+#[tokio::test]
+async fn test_fetch_file_sync_without_overload() {
+    let fetcher = get_fetcher();
+    let reporter = SharedErrorReporter::default();
+    let mut source = get_source("toolkit", &fetcher);
+    source.set_reporter(reporter.clone());
+
+    let pending = source.fetch_file(&langid!("pl"), &FTL_RESOURCE.into());
+    let option = source.fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), false);
+    assert!(option.is_pending());
+    assert!(matches!(
+        &reporter.errors.lock().unwrap()[..],
+        [L10nRegistryError::PendingAsyncLoad { .. }]
+    ));
+
+    // The sync load left the pending load alone.
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 0);
+    let option = match option {
+        ResourceOption::Pending(future) => future.await,
+        _ => unreachable!(),
+    };
+    assert!(option.is_some());
+    assert!(pending.await.is_some());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_directory_fetcher() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("pl/toolkit")).unwrap();
+    std::fs::write(dir.path().join("pl/toolkit/menu.ftl"), "menu-file = Plik\n").unwrap();
+
+    let source = FileSource::new(
+        "toolkit".to_string(),
+        None,
+        vec![langid!("pl")],
+        "{locale}/".to_string(),
+        FileSourceOptions::default(),
+        DirectoryFileFetcher::new(dir.path()),
+    );
+    let worker = {
+        let source = source.clone();
+        thread::spawn(move || {
+            source
+                .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+                .is_some()
+        })
+    };
+    assert!(worker.join().unwrap());
+    assert!(source
+        .fetch_file_sync(&langid!("pl"), &"toolkit/missing.ftl".into(), true)
+        .is_required_and_missing());
+}
+
+#[tokio::test]
+async fn test_preload_and_invalidate() {
+    let fetcher = get_fetcher();
+    let reg = get_registry(&fetcher, SharedErrorReporter::default());
+    let resource_ids: Vec<ResourceId> = vec![FTL_RESOURCE.into(), "missing.ftl".into()];
+
+    let report = reg
+        .preload(&[langid!("pl"), langid!("de")], &resource_ids)
+        .await;
+    let toolkit = report.get_source("toolkit").unwrap();
+    assert_eq!(toolkit.loaded, vec![(langid!("pl"), FTL_RESOURCE.into())]);
+    assert_eq!(toolkit.missing, vec![(langid!("pl"), "missing.ftl".into())]);
+    assert!(toolkit.failed.is_empty());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 2);
+
+    // Preloaded resources are served from the cache.
+    let report = reg.preload_sync(&[langid!("pl")], &resource_ids);
+    assert_eq!(report.get_source("toolkit").unwrap().loaded.len(), 1);
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 2);
+    let stats = reg.stats();
+    assert_eq!(stats.cache_misses, 2);
+    assert_eq!(stats.cache_hits, 2);
+
+    reg.invalidate_resources(&[langid!("pl")], &[FTL_RESOURCE.into()]);
+    let source = reg.get_source("toolkit").unwrap().unwrap();
+    assert_eq!(source.has_file(&langid!("pl"), &FTL_RESOURCE.into()), None);
+    assert!(source
+        .fetch_file_sync(&langid!("pl"), &FTL_RESOURCE.into(), true)
+        .is_some());
+    assert_eq!(fetcher.fetches.load(Ordering::SeqCst), 3);
+
+    source.clear_cache();
+    assert_eq!(source.cached_bytes(), 0);
+}
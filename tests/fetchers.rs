@@ -0,0 +1,1057 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use l10nregistry::errors::L10nRegistryError;
+use l10nregistry::fetchers::{
+    DirectoryFileFetcher, DirectoryWatcher, MemoryFileFetcher, OverlayFileFetcher,
+};
+use l10nregistry::registry::L10nRegistry;
+use l10nregistry::source::{
+    build_index_from_directory, read_index, write_index, CachePolicy, FileFetcher, FileSource,
+    FileSourceBuilder, FileSourceOptions, PathTemplate, ResourceId, ResourceOption, StartupCache,
+};
+use l10nregistry::testing::TestEnvironment;
+use unic_langid::LanguageIdentifier;
+
+static FTL_RESOURCE: &str = "browser/menu.ftl";
+static FTL_RESOURCE_MISSING: &str = "missing.ftl";
+
+fn get_locale_dir() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (locale, value) in &[("en-US", "File"), ("pl", "Plik")] {
+        let path = dir.path().join(locale).join("browser");
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("menu.ftl"), format!("menu-file = {}\n", value)).unwrap();
+    }
+    dir
+}
+
+/// Wraps a fetcher, recording the paths requested by each `fetch_many` call.
+#[derive(Clone)]
+struct BatchRecordingFetcher<F> {
+    inner: F,
+    batches: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl<F> BatchRecordingFetcher<F> {
+    fn new(inner: F) -> Self {
+        Self {
+            inner,
+            batches: Default::default(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<F: FileFetcher> FileFetcher for BatchRecordingFetcher<F> {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch(resource_id).await
+    }
+
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        self.batches
+            .borrow_mut()
+            .push(resource_ids.iter().map(|id| id.value.clone()).collect());
+        self.inner.fetch_many(resource_ids).await
+    }
+}
+
+#[test]
+fn test_directory_fetch_sync() {
+    let dir = get_locale_dir();
+    let fetcher = DirectoryFileFetcher::new(dir.path());
+
+    let source = fetcher
+        .fetch_sync(&"en-US/browser/menu.ftl".into())
+        .unwrap();
+    assert_eq!(source, "menu-file = File\n");
+
+    let err = fetcher.fetch_sync(&"en-US/missing.ftl".into()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    // Reading a directory is not the same as a missing file.
+    let err = fetcher.fetch_sync(&"en-US/browser".into()).unwrap_err();
+    assert_ne!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_directory_fetch_outside_root() {
+    let dir = get_locale_dir();
+    let fetcher = DirectoryFileFetcher::new(dir.path().join("en-US"));
+
+    let err = fetcher
+        .fetch_sync(&"../pl/browser/menu.ftl".into())
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // A single leading `/` is relative to the root, but not two.
+    let source = fetcher.fetch_sync(&"/browser/menu.ftl".into()).unwrap();
+    assert_eq!(source, "menu-file = File\n");
+    let absolute: ResourceId =
+        format!("/{}", dir.path().join("pl/browser/menu.ftl").display()).into();
+    let err = fetcher.fetch_sync(&absolute).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn test_directory_fetch_async() {
+    let dir = get_locale_dir();
+    let fetcher = DirectoryFileFetcher::new(dir.path());
+
+    let source = fetcher.fetch(&"pl/browser/menu.ftl".into()).await.unwrap();
+    assert_eq!(source, "menu-file = Plik\n");
+
+    let err = fetcher.fetch(&"pl/missing.ftl".into()).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[tokio::test]
+async fn test_directory_file_source() {
+    let dir = get_locale_dir();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+
+    let fs1 = FileSource::new(
+        "browser".to_string(),
+        None,
+        vec![en_us.clone(), pl.clone()],
+        "{locale}/".to_string(),
+        Default::default(),
+        DirectoryFileFetcher::new(dir.path()),
+    );
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert!(fs1.fetch_file(&pl, &FTL_RESOURCE.into()).await.is_some());
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), false)
+        .is_required_and_missing());
+
+    // The absolute `pre_path` form is resolved against the root as well.
+    let fs2 = FileSource::new(
+        "browser".to_string(),
+        None,
+        vec![pl.clone()],
+        "/{locale}/".to_string(),
+        Default::default(),
+        DirectoryFileFetcher::new(dir.path()),
+    );
+    assert!(fs2.fetch_file(&pl, &FTL_RESOURCE.into()).await.is_some());
+}
+
+#[cfg(feature = "archive")]
+mod archive {
+    use super::*;
+    use l10nregistry::errors::L10nRegistrySetupError;
+    use l10nregistry::fetchers::ArchiveFileFetcher;
+    use std::io::{Cursor, Write};
+    use zip::{write::FileOptions, ZipWriter};
+
+    fn get_archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        writer
+            .add_directory("localization/en-US/browser/", FileOptions::default())
+            .unwrap();
+        writer
+            .start_file(
+                "localization/en-US/browser/menu.ftl",
+                FileOptions::default(),
+            )
+            .unwrap();
+        writer.write_all(b"menu-file = File\n").unwrap();
+        writer
+            .start_file("localization/pl/browser/menu.ftl", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"menu-file = Plik\n").unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_archive_fetch_sync() {
+        let fetcher = ArchiveFileFetcher::from_bytes(get_archive()).unwrap();
+
+        let source = fetcher
+            .fetch_sync(&"localization/pl/browser/menu.ftl".into())
+            .unwrap();
+        assert_eq!(source, "menu-file = Plik\n");
+
+        let err = fetcher
+            .fetch_sync(&"localization/pl/missing.ftl".into())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_archive_invalid() {
+        let err = ArchiveFileFetcher::from_bytes(b"not a zip".to_vec())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_archive_index() {
+        let fetcher = ArchiveFileFetcher::from_bytes(get_archive()).unwrap();
+        let mut index = fetcher.index();
+        index.sort();
+        assert_eq!(
+            index,
+            vec![
+                "localization/en-US/browser/menu.ftl",
+                "localization/pl/browser/menu.ftl",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_archive_file_source() {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let pl: LanguageIdentifier = "pl".parse().unwrap();
+
+        let fs1 = ArchiveFileFetcher::from_bytes(get_archive())
+            .unwrap()
+            .into_file_source(
+                "browser".to_string(),
+                None,
+                vec![en_us.clone(), pl.clone()],
+                "localization/{locale}/".to_string(),
+                Default::default(),
+            )
+            .unwrap();
+
+        assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE.into()), Some(true));
+        assert_eq!(
+            fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()),
+            Some(false)
+        );
+        assert!(fs1
+            .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+            .is_some());
+        assert!(fs1.fetch_file(&pl, &FTL_RESOURCE.into()).await.is_some());
+    }
+
+    #[test]
+    fn test_archive_file_source_leading_slash() {
+        let pl: LanguageIdentifier = "pl".parse().unwrap();
+
+        let fs1 = ArchiveFileFetcher::from_bytes(get_archive())
+            .unwrap()
+            .into_file_source(
+                "browser".to_string(),
+                None,
+                vec![pl.clone()],
+                "/localization/{locale}/".to_string(),
+                Default::default(),
+            )
+            .unwrap();
+        assert_eq!(fs1.has_file(&pl, &FTL_RESOURCE.into()), Some(true));
+        assert!(fs1
+            .fetch_file_sync(&pl, &FTL_RESOURCE.into(), false)
+            .is_some());
+
+        let err = ArchiveFileFetcher::from_bytes(get_archive())
+            .unwrap()
+            .into_file_source(
+                "browser".to_string(),
+                None,
+                vec![pl],
+                "localization/{unknown}/".to_string(),
+                Default::default(),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            L10nRegistrySetupError::UnknownPlaceholder { .. }
+        ));
+    }
+}
+
+#[cfg(feature = "compression")]
+mod compression {
+    use super::*;
+    use l10nregistry::fetchers::DecompressingFileFetcher;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+            writer.write_all(data).unwrap();
+        }
+        output
+    }
+
+    fn get_compressed_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            ("en-US/browser/menu.ftl.gz", gzip(b"menu-file = File\n")),
+            ("pl/browser/menu.ftl.br", brotli(b"menu-file = Plik\n")),
+            // Gzip data is recognized by its header, whatever its name.
+            ("de/browser/menu.ftl", gzip(b"menu-file = Datei\n")),
+            ("fr/browser/menu.ftl.gz", b"\x1f\x8bnot gzip".to_vec()),
+        ];
+        for (path, data) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_decompress_sync() {
+        let dir = get_compressed_dir();
+        let fetcher = DecompressingFileFetcher::new(DirectoryFileFetcher::new(dir.path()));
+
+        for (path, expected) in &[
+            ("en-US/browser/menu.ftl", "menu-file = File\n"),
+            ("en-US/browser/menu.ftl.gz", "menu-file = File\n"),
+            ("pl/browser/menu.ftl", "menu-file = Plik\n"),
+            ("de/browser/menu.ftl", "menu-file = Datei\n"),
+        ] {
+            assert_eq!(fetcher.fetch_sync(&(*path).into()).unwrap(), *expected);
+        }
+
+        let err = fetcher
+            .fetch_sync(&"fr/browser/menu.ftl".into())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = fetcher
+            .fetch_sync(&"en-US/browser/missing.ftl".into())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // Data expanding past the limit is rejected rather than read whole.
+        let fetcher = DecompressingFileFetcher::new(DirectoryFileFetcher::new(dir.path()))
+            .max_size("menu-file = File\n".len());
+        assert!(fetcher.fetch_sync(&"en-US/browser/menu.ftl".into()).is_ok());
+        let fetcher = fetcher.max_size("menu-file = File".len());
+        for path in &["en-US/browser/menu.ftl", "pl/browser/menu.ftl"] {
+            let err = fetcher.fetch_sync(&(*path).into()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decompress_many() {
+        let dir = get_compressed_dir();
+        let inner = BatchRecordingFetcher::new(DirectoryFileFetcher::new(dir.path()));
+        let fetcher = DecompressingFileFetcher::new(inner.clone());
+
+        let paths = [
+            "en-US/browser/menu.ftl",
+            "en-US/browser/menu.ftl.gz",
+            "pl/browser/menu.ftl",
+            "de/browser/menu.ftl",
+            "fr/browser/menu.ftl",
+            "en-US/browser/missing.ftl",
+        ];
+        let resource_ids: Vec<ResourceId> = paths.iter().map(|&path| path.into()).collect();
+        let results = fetcher.fetch_many(&resource_ids).await;
+
+        let sources: Vec<_> = results
+            .iter()
+            .take(4)
+            .map(|result| String::from_utf8(result.as_ref().unwrap().clone()).unwrap())
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                "menu-file = File\n",
+                "menu-file = File\n",
+                "menu-file = Plik\n",
+                "menu-file = Datei\n",
+            ]
+        );
+        assert_eq!(
+            results[4].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            results[5].as_ref().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // Each round only requests the next candidate of the resources which
+        // haven't been found yet.
+        assert_eq!(
+            *inner.batches.borrow(),
+            vec![
+                paths.to_vec(),
+                vec![
+                    "en-US/browser/menu.ftl.gz",
+                    "pl/browser/menu.ftl.gz",
+                    "fr/browser/menu.ftl.gz",
+                    "en-US/browser/missing.ftl.gz",
+                ],
+                vec!["pl/browser/menu.ftl.br", "en-US/browser/missing.ftl.br"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decompressing_file_source() {
+        let dir = get_compressed_dir();
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let fr: LanguageIdentifier = "fr".parse().unwrap();
+
+        let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone(), fr.clone()], "{locale}/")
+            .build(DecompressingFileFetcher::new(DirectoryFileFetcher::new(
+                dir.path(),
+            )))
+            .unwrap();
+
+        assert!(fs1.fetch_file(&en_us, &FTL_RESOURCE.into()).await.is_some());
+        assert!(fs1
+            .fetch_file(&fr, &FTL_RESOURCE.into())
+            .await
+            .is_fetch_failed());
+    }
+}
+
+#[tokio::test]
+async fn test_memory_file_source() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher: MemoryFileFetcher = vec![("en-US/browser/menu.ftl", "menu-file = File\n")]
+        .into_iter()
+        .collect();
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .build(fetcher.clone())
+        .unwrap();
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert!(fs1
+        .fetch_file(&en_us, &FTL_RESOURCE_MISSING.into())
+        .await
+        .is_required_and_missing());
+
+    // Entries can be added after the source has been created.
+    fetcher.insert("en-US/generated.ftl", "generated = Generated\n");
+    assert!(fs1
+        .fetch_file(&en_us, &"generated.ftl".into())
+        .await
+        .is_some());
+}
+
+#[test]
+fn test_memory_fetch_sync() {
+    let fetcher = MemoryFileFetcher::new();
+    assert!(fetcher.is_empty());
+
+    assert_eq!(fetcher.insert("main.ftl", "key = Value"), None);
+    assert_eq!(
+        fetcher.insert("main.ftl", "key = Value 2"),
+        Some("key = Value".to_string())
+    );
+    assert_eq!(fetcher.len(), 1);
+    assert_eq!(
+        fetcher.fetch_sync(&"main.ftl".into()).unwrap(),
+        "key = Value 2"
+    );
+
+    assert_eq!(
+        fetcher.remove("main.ftl"),
+        Some("key = Value 2".to_string())
+    );
+    let err = fetcher.fetch_sync(&"main.ftl".into()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_builder_with_index() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("en-US/browser/menu.ftl", "menu-file = File\n");
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .metasource("langpack")
+        .options(FileSourceOptions {
+            allow_override: true,
+            ..Default::default()
+        })
+        .index(fetcher.index())
+        .build(fetcher)
+        .unwrap();
+
+    assert_eq!(fs1.metasource, "langpack");
+    assert!(fs1.options().allow_override);
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE.into()), Some(true));
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()),
+        Some(false)
+    );
+}
+
+#[test]
+fn test_memory_file_source_cache_policy() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let fetcher: MemoryFileFetcher = vec![
+        ("en-US/browser/menu.ftl", "menu-file = File\n"),
+        ("pl/browser/menu.ftl", "menu-file = Plik\n"),
+    ]
+    .into_iter()
+    .collect();
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone(), pl.clone()], "{locale}/")
+        .options(FileSourceOptions {
+            cache_policy: CachePolicy::Locales { max_locales: 1 },
+            ..Default::default()
+        })
+        .build(fetcher)
+        .unwrap();
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE.into()), Some(true));
+
+    assert!(fs1
+        .fetch_file_sync(&pl, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert_eq!(fs1.has_file(&pl, &FTL_RESOURCE.into()), Some(true));
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE.into()), None);
+}
+
+#[tokio::test]
+async fn test_memory_file_source_invalidate() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let fetcher: MemoryFileFetcher = vec![
+        ("en-US/browser/menu.ftl", "menu-file = File\n"),
+        ("pl/browser/menu.ftl", "menu-file = Plik\n"),
+    ]
+    .into_iter()
+    .collect();
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone(), pl.clone()], "{locale}/")
+        .build(fetcher.clone())
+        .unwrap();
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), false)
+        .is_none());
+    fetcher.insert("en-US/missing.ftl", "missing = No more\n");
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), false)
+        .is_none());
+
+    fs1.invalidate(&en_us, &FTL_RESOURCE_MISSING.into());
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()), None);
+    assert!(fs1
+        .fetch_file(&en_us, &FTL_RESOURCE_MISSING.into())
+        .await
+        .is_some());
+
+    assert!(fs1.fetch_file(&pl, &FTL_RESOURCE.into()).await.is_some());
+    fs1.invalidate_locale(&pl);
+    assert_eq!(fs1.has_file(&pl, &FTL_RESOURCE.into()), None);
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()),
+        Some(true)
+    );
+
+    fs1.clear_cache();
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()), None);
+    assert_eq!(fs1.cached_bytes(), 0);
+}
+
+#[tokio::test]
+async fn test_invalidate_while_loading() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("en-US/browser/menu.ftl", "menu-file = File\n");
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .build(fetcher)
+        .unwrap();
+
+    let pending = fs1.fetch_file(&en_us, &FTL_RESOURCE.into());
+    fs1.invalidate(&en_us, &FTL_RESOURCE.into());
+
+    // The load started before the invalidation still resolves, but doesn't
+    // end up in the cache.
+    assert!(pending.await.is_some());
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE.into()), None);
+
+    // A new load does.
+    assert!(fs1.fetch_file(&en_us, &FTL_RESOURCE.into()).await.is_some());
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE.into()), Some(true));
+}
+
+#[test]
+fn test_directory_watcher() {
+    let dir = get_locale_dir();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let reg: L10nRegistry<(), ()> = L10nRegistry::with_provider(());
+    reg.register_sources(vec![FileSourceBuilder::new(
+        "browser",
+        vec![en_us.clone()],
+        "{locale}/",
+    )
+    .build(DirectoryFileFetcher::new(dir.path()))
+    .unwrap()])
+        .unwrap();
+
+    let notified = Rc::new(RefCell::new(vec![]));
+    let listener_notified = notified.clone();
+    reg.add_change_listener(move |source, paths| {
+        listener_notified
+            .borrow_mut()
+            .push((source.name.clone(), paths.to_vec()));
+    });
+
+    let mut watcher = DirectoryWatcher::polling(dir.path(), vec!["browser".to_string()]).unwrap();
+    assert!(watcher.update_registry(&reg).unwrap().is_empty());
+
+    let get_source = || {
+        let source = reg.get_source("browser").unwrap().unwrap();
+        match source.fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false) {
+            ResourceOption::Some(res) => res.source().to_string(),
+            _ => panic!("Expected a resource"),
+        }
+    };
+    assert_eq!(get_source(), "menu-file = File\n");
+
+    fs::write(
+        dir.path().join("en-US/browser/menu.ftl"),
+        "menu-file = File...\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("en-US/new.ftl"), "new = New\n").unwrap();
+    fs::remove_file(dir.path().join("pl/browser/menu.ftl")).unwrap();
+
+    let changes = vec![
+        "en-US/browser/menu.ftl".to_string(),
+        "en-US/new.ftl".to_string(),
+        "pl/browser/menu.ftl".to_string(),
+    ];
+    assert_eq!(watcher.update_registry(&reg).unwrap(), changes);
+    assert_eq!(
+        notified.borrow().as_slice(),
+        &[("browser".to_string(), changes)]
+    );
+    assert_eq!(get_source(), "menu-file = File...\n");
+
+    assert!(watcher.update_registry(&reg).unwrap().is_empty());
+    assert_eq!(notified.borrow().len(), 1);
+}
+
+#[test]
+fn test_directory_watcher_leading_slash() {
+    let dir = get_locale_dir();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let reg: L10nRegistry<(), ()> = L10nRegistry::with_provider(());
+    reg.register_sources(vec![FileSourceBuilder::new(
+        "browser",
+        vec![en_us.clone()],
+        "/{locale}/",
+    )
+    .build(DirectoryFileFetcher::new(dir.path()))
+    .unwrap()])
+        .unwrap();
+
+    let notified = Rc::new(RefCell::new(vec![]));
+    let listener_notified = notified.clone();
+    reg.add_change_listener(move |_, paths| {
+        listener_notified.borrow_mut().extend(paths.to_vec());
+    });
+
+    let mut watcher = DirectoryWatcher::polling(dir.path(), vec!["browser".to_string()]).unwrap();
+    let source = reg.get_source("browser").unwrap().unwrap();
+    assert!(source
+        .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert_eq!(source.has_file(&en_us, &FTL_RESOURCE.into()), Some(true));
+
+    fs::write(
+        dir.path().join("en-US/browser/menu.ftl"),
+        "menu-file = File...\n",
+    )
+    .unwrap();
+    assert_eq!(
+        watcher.update_registry(&reg).unwrap(),
+        vec!["en-US/browser/menu.ftl".to_string()]
+    );
+    assert_eq!(
+        notified.borrow().as_slice(),
+        &["/en-US/browser/menu.ftl".to_string()]
+    );
+    assert_eq!(source.has_file(&en_us, &FTL_RESOURCE.into()), None);
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn test_directory_watcher_native() {
+    // The watched root is relative, while events carry absolute paths.
+    let dir = tempfile::tempdir_in("target").unwrap();
+    let cwd = std::env::current_dir().unwrap();
+    let root = dir.path().strip_prefix(cwd).unwrap();
+    fs::create_dir_all(root.join("en-US")).unwrap();
+
+    let mut watcher = DirectoryWatcher::new(root, vec![]).unwrap();
+    assert!(watcher.root().is_absolute());
+    fs::write(root.join("en-US/menu.ftl"), "menu-file = File\n").unwrap();
+
+    // Events are delivered in the background.
+    let expected = "en-US/menu.ftl".to_string();
+    let mut changes = vec![];
+    for _ in 0..100 {
+        changes.extend(watcher.poll_changes().unwrap());
+        if changes.contains(&expected) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(
+        changes.contains(&expected),
+        "Unexpected changes: {:?}",
+        changes
+    );
+}
+
+#[test]
+fn test_build_index_from_directory() {
+    let dir = get_locale_dir();
+    fs::write(dir.path().join("en-US/main.ftl"), "main = Main\n").unwrap();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let de: LanguageIdentifier = "de".parse().unwrap();
+    let locales = vec![en_us.clone(), pl.clone(), de.clone()];
+
+    let index = build_index_from_directory(
+        dir.path(),
+        &locales,
+        &PathTemplate::new("{locale}/", &Default::default()).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        index,
+        vec![
+            "en-US/browser/menu.ftl",
+            "en-US/main.ftl",
+            "pl/browser/menu.ftl",
+        ]
+    );
+
+    let mut manifest = vec![];
+    write_index(&mut manifest, &index).unwrap();
+    let mut manifest = String::from_utf8(manifest).unwrap();
+    manifest.insert_str(0, "# Generated\n\n");
+    let index = read_index(manifest.as_bytes()).unwrap();
+
+    let fs1 = FileSource::new_with_index(
+        "browser".to_string(),
+        None,
+        locales,
+        "{locale}/".to_string(),
+        Default::default(),
+        DirectoryFileFetcher::new(dir.path()),
+        index,
+    );
+    assert_eq!(fs1.has_file(&en_us, &"main.ftl".into()), Some(true));
+    assert_eq!(fs1.has_file(&pl, &"main.ftl".into()), Some(false));
+    assert_eq!(fs1.has_file(&pl, &FTL_RESOURCE.into()), Some(true));
+    assert_eq!(fs1.has_file(&de, &FTL_RESOURCE.into()), Some(false));
+    assert!(fs1
+        .fetch_file_sync(&pl, &FTL_RESOURCE.into(), false)
+        .is_some());
+}
+
+#[tokio::test]
+async fn test_directory_encoding() {
+    let dir = get_locale_dir();
+    fs::write(
+        dir.path().join("en-US/bom.ftl"),
+        b"\xEF\xBB\xBFmenu-file = File\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("en-US/latin1.ftl"), b"menu-file = Fi\xE9\n").unwrap();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .reporter(env.clone())
+        .build(DirectoryFileFetcher::new(dir.path()))
+        .unwrap();
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"bom.ftl".into(), false)
+        .is_some());
+    assert!(env.errors().is_empty());
+
+    let file = fs1.fetch_file(&en_us, &"latin1.ftl".into()).await;
+    assert!(file.is_fetch_failed());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::InvalidEncoding {
+            resource_id: "en-US/latin1.ftl".into(),
+            locale: en_us,
+            valid_up_to: 14,
+        }]
+    );
+}
+
+#[test]
+fn test_memory_bom() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("en-US/bom.ftl", "\u{FEFF}menu-file = File\n");
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .reporter(env.clone())
+        .build(fetcher)
+        .unwrap();
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"bom.ftl".into(), false)
+        .is_some());
+    assert!(env.errors().is_empty());
+}
+
+#[test]
+fn test_startup_cache() {
+    let dir = get_locale_dir();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let get_registry_with_stamps = |record_stamps| {
+        let reg: L10nRegistry<(), ()> = L10nRegistry::with_provider(());
+        reg.register_sources(vec![FileSourceBuilder::new(
+            "app",
+            vec![en_us.clone(), pl.clone()],
+            "{locale}/",
+        )
+        .options(FileSourceOptions {
+            record_stamps,
+            ..Default::default()
+        })
+        .build(DirectoryFileFetcher::new(dir.path()))
+        .unwrap()])
+            .unwrap();
+        reg
+    };
+    let get_registry = || get_registry_with_stamps(true);
+
+    // Without stamps, the saved resources can't be checked, so none of them
+    // are seeded.
+    let reg = get_registry_with_stamps(false);
+    reg.preload_sync(&[en_us.clone(), pl.clone()], &[FTL_RESOURCE.into()]);
+    let mut cache = StartupCache::new("build-1");
+    reg.save_startup_cache(&mut cache);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(get_registry().seed_startup_cache(&cache), 0);
+
+    // The first session loads the resources and saves them.
+    let reg = get_registry();
+    reg.preload_sync(&[en_us.clone(), pl.clone()], &[FTL_RESOURCE.into()]);
+    let mut cache = StartupCache::new("build-1");
+    reg.save_startup_cache(&mut cache);
+    assert_eq!(cache.len(), 2);
+    let mut blob = vec![];
+    cache.write(&mut blob).unwrap();
+
+    // The `pl` file changes between sessions.
+    fs::write(
+        dir.path().join("pl/browser/menu.ftl"),
+        "menu-file = Plik...\n",
+    )
+    .unwrap();
+
+    let reg = get_registry();
+    let cache = StartupCache::read(blob.as_slice(), "build-1").unwrap();
+    assert_eq!(reg.seed_startup_cache(&cache), 1);
+    let source = reg.get_source("app").unwrap().unwrap();
+    assert!(source
+        .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert_eq!(source.stats().fetches, 0);
+
+    let res = match source.fetch_file_sync(&pl, &FTL_RESOURCE.into(), false) {
+        ResourceOption::Some(res) => res,
+        _ => panic!("Expected a resource"),
+    };
+    assert_eq!(res.source(), "menu-file = Plik...\n");
+    assert_eq!(source.stats().fetches, 1);
+
+    // A file changing after it was loaded is saved with its old stamp.
+    fs::write(
+        dir.path().join("en-US/browser/menu.ftl"),
+        "menu-file = File...\n",
+    )
+    .unwrap();
+    let mut cache = StartupCache::new("build-1");
+    reg.save_startup_cache(&mut cache);
+    assert_eq!(cache.len(), 2);
+
+    let reg = get_registry();
+    assert_eq!(reg.seed_startup_cache(&cache), 1);
+    let source = reg.get_source("app").unwrap().unwrap();
+    let res = match source.fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false) {
+        ResourceOption::Some(res) => res,
+        _ => panic!("Expected a resource"),
+    };
+    assert_eq!(res.source(), "menu-file = File...\n");
+    assert_eq!(source.stats().fetches, 1);
+
+    // A cache saved by another build is ignored.
+    let reg = get_registry();
+    let cache = StartupCache::read(blob.as_slice(), "build-2").unwrap();
+    assert_eq!(reg.seed_startup_cache(&cache), 0);
+}
+
+fn get_overlay(dir: &tempfile::TempDir) -> OverlayFileFetcher {
+    let packaged: MemoryFileFetcher = vec![
+        ("en-US/browser/menu.ftl", "menu-file = Packaged File\n"),
+        ("en-US/browser/edit.ftl", "menu-edit = Edit\n"),
+    ]
+    .into_iter()
+    .collect();
+    OverlayFileFetcher::new()
+        .layer("user", DirectoryFileFetcher::new(dir.path()))
+        .layer("app", packaged)
+}
+
+#[test]
+fn test_overlay_fetch_sync() {
+    let dir = get_locale_dir();
+    let fetcher = get_overlay(&dir);
+    assert_eq!(fetcher.layer_names(), vec!["user", "app"]);
+
+    let source = fetcher
+        .fetch_sync(&"en-US/browser/menu.ftl".into())
+        .unwrap();
+    assert_eq!(source, "menu-file = File\n");
+    assert_eq!(fetcher.served_by("en-US/browser/menu.ftl"), Some("user"));
+
+    let source = fetcher
+        .fetch_sync(&"en-US/browser/edit.ftl".into())
+        .unwrap();
+    assert_eq!(source, "menu-edit = Edit\n");
+    assert_eq!(fetcher.served_by("en-US/browser/edit.ftl"), Some("app"));
+
+    let err = fetcher
+        .fetch_sync(&"en-US/browser/missing.ftl".into())
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(fetcher.served_by("en-US/browser/missing.ftl"), None);
+
+    // An error other than a missing file isn't hidden by the lower layers.
+    fs::create_dir_all(dir.path().join("en-US/browser/edit.ftl")).unwrap();
+    let err = fetcher
+        .fetch_sync(&"en-US/browser/edit.ftl".into())
+        .unwrap_err();
+    assert_ne!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(fetcher.served_by("en-US/browser/edit.ftl"), None);
+}
+
+#[tokio::test]
+async fn test_overlay_file_source() {
+    let dir = get_locale_dir();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = get_overlay(&dir);
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .build(fetcher.clone())
+        .unwrap();
+    assert!(fs1.fetch_file(&en_us, &FTL_RESOURCE.into()).await.is_some());
+    assert!(fs1
+        .fetch_file(&en_us, &"browser/edit.ftl".into())
+        .await
+        .is_some());
+
+    assert_eq!(fetcher.served_by("en-US/browser/menu.ftl"), Some("user"));
+    assert_eq!(fetcher.served_by("en-US/browser/edit.ftl"), Some("app"));
+}
+
+#[tokio::test]
+async fn test_overlay_fetch_many() {
+    let dir = get_locale_dir();
+    let user = BatchRecordingFetcher::new(DirectoryFileFetcher::new(dir.path()));
+    let app = BatchRecordingFetcher::new(
+        vec![("en-US/browser/edit.ftl", "menu-edit = Edit\n")]
+            .into_iter()
+            .collect::<MemoryFileFetcher>(),
+    );
+    let fetcher = OverlayFileFetcher::new()
+        .layer("user", user.clone())
+        .layer("app", app.clone());
+
+    let paths = [
+        "en-US/browser/menu.ftl",
+        "en-US/browser/edit.ftl",
+        "en-US/browser/missing.ftl",
+    ];
+    let resource_ids: Vec<ResourceId> = paths.iter().map(|&path| path.into()).collect();
+    let results = fetcher.fetch_many(&resource_ids).await;
+    assert_eq!(results[0].as_ref().unwrap(), b"menu-file = File\n");
+    assert_eq!(results[1].as_ref().unwrap(), b"menu-edit = Edit\n");
+    assert_eq!(
+        results[2].as_ref().unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    // Each layer is only asked for the resources the ones above didn't have.
+    assert_eq!(*user.batches.borrow(), vec![paths.to_vec()]);
+    assert_eq!(*app.batches.borrow(), vec![paths[1..].to_vec()]);
+    assert_eq!(fetcher.served_by(paths[0]), Some("user"));
+    assert_eq!(fetcher.served_by(paths[1]), Some("app"));
+    assert_eq!(fetcher.served_by(paths[2]), None);
+}
+
+#[test]
+fn test_overlay_errors_name_the_layer() {
+    let dir = get_locale_dir();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    fs::write(
+        dir.path().join("en-US/browser/menu.ftl"),
+        "menu-file = File\n!!!\n",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("en-US/browser/edit.ftl")).unwrap();
+
+    let fs1 = FileSourceBuilder::new("browser", vec![en_us.clone()], "{locale}/")
+        .reporter(env.clone())
+        .build(get_overlay(&dir))
+        .unwrap();
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE.into(), false)
+        .is_some());
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"browser/edit.ftl".into(), false)
+        .is_fetch_failed());
+
+    let errors = env.errors();
+    assert_eq!(errors.len(), 2);
+    for error in &errors {
+        match error {
+            L10nRegistryError::FluentError { origin, .. }
+            | L10nRegistryError::FetchError { origin, .. } => {
+                assert_eq!(origin.as_deref(), Some("user"));
+            }
+            _ => panic!("Unexpected error: {}", error),
+        }
+        assert!(error.to_string().ends_with(" (served by user)"));
+    }
+}
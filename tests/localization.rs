@@ -26,13 +26,14 @@ const L10N_ID_ONLY_EN: (&str, Option<&str>) = (
 );
 
 fn get_file_fetcher() -> &'static TestFileFetcher {
-    let fetcher: &mut Option<TestFileFetcher> = unsafe { &mut FILE_FETCHER };
+    let fetcher: &mut Option<TestFileFetcher> =
+        unsafe { &mut *std::ptr::addr_of_mut!(FILE_FETCHER) };
 
-    fetcher.get_or_insert_with(|| TestFileFetcher::new())
+    fetcher.get_or_insert_with(TestFileFetcher::new)
 }
 
 fn get_l10n_registry() -> &'static L10nRegistry {
-    let reg: &mut Option<L10nRegistry> = unsafe { &mut L10N_REGISTRY };
+    let reg: &mut Option<L10nRegistry> = unsafe { &mut *std::ptr::addr_of_mut!(L10N_REGISTRY) };
 
     reg.get_or_insert_with(|| {
         let fetcher = get_file_fetcher();
@@ -105,7 +106,7 @@ fn localization_format_value_sync() {
         let value = bundles
             .format_value_sync(query.0, None, &mut errors)
             .unwrap();
-        assert_eq!(value, query.1.map(|s| Cow::Borrowed(s)));
+        assert_eq!(value, query.1.map(Cow::Borrowed));
     }
 
     assert_eq!(errors.len(), 4);
@@ -190,12 +191,12 @@ async fn localization_upgrade() {
     let value = bundles
         .format_value_sync(L10N_ID_PL_EN.0, None, &mut errors)
         .unwrap();
-    assert_eq!(value, L10N_ID_PL_EN.1.map(|s| Cow::Borrowed(s)));
+    assert_eq!(value, L10N_ID_PL_EN.1.map(Cow::Borrowed));
 
     loc.set_async();
     let bundles = loc.bundles();
     let value = bundles
         .format_value(L10N_ID_PL_EN.0, None, &mut errors)
         .await;
-    assert_eq!(value, L10N_ID_PL_EN.1.map(|s| Cow::Borrowed(s)));
+    assert_eq!(value, L10N_ID_PL_EN.1.map(Cow::Borrowed));
 }
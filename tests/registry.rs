@@ -1,4 +1,18 @@
-use l10nregistry::testing::{FileSource, RegistrySetup, TestFileFetcher};
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use l10nregistry::env::Timer;
+use l10nregistry::fetchers::MemoryFileFetcher;
+use l10nregistry::registry::{L10nRegistry, PreloadReport};
+use l10nregistry::source::{FileFetcher, FileSourceBuilder, FileSourceOptions, ResourceId};
+use l10nregistry::testing::{
+    FileSource, MockBundleAdapter, RegistrySetup, TestEnvironment, TestFileFetcher,
+};
 use unic_langid::LanguageIdentifier;
 
 static FTL_RESOURCE_TOOLKIT: &str = "toolkit/global/textActions.ftl";
@@ -294,3 +308,579 @@ async fn test_generate_bundles_with_metasources() {
     assert!(i.next().await.is_some());
     assert!(i.next().await.is_none());
 }
+
+#[test]
+fn test_invalidate_resources() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("browser/en-US/menu.ftl", "menu-file = File");
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::new(vec![en_us.clone()]));
+    reg.register_sources(vec![FileSourceBuilder::new(
+        "browser",
+        vec![en_us.clone()],
+        "browser/{locale}/",
+    )
+    .build(fetcher.clone())
+    .unwrap()])
+        .unwrap();
+
+    let paths = vec!["menu.ftl".into()];
+    let bundle = reg
+        .generate_bundles_for_lang_sync(en_us.clone(), paths.clone())
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(bundle.has_message("menu-file"));
+
+    fetcher.insert("browser/en-US/menu.ftl", "menu-open = Open");
+
+    let bundle = reg
+        .generate_bundles_for_lang_sync(en_us.clone(), paths.clone())
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(!bundle.has_message("menu-open"));
+
+    // Listeners are notified, and may register further listeners.
+    let notified = Rc::new(RefCell::new(vec![]));
+    let listener_notified = notified.clone();
+    let listener_reg = reg.clone();
+    reg.add_change_listener(move |source, full_paths| {
+        listener_notified
+            .borrow_mut()
+            .push((source.name.clone(), full_paths.to_vec()));
+        listener_reg.add_change_listener(|_, _| {});
+    });
+
+    let locales = vec![en_us.clone()];
+    reg.invalidate_resources(&locales, &paths);
+    assert_eq!(
+        notified.borrow().as_slice(),
+        &[(
+            "browser".to_string(),
+            vec!["browser/en-US/menu.ftl".to_string()]
+        )]
+    );
+
+    let bundle = reg
+        .generate_bundles_for_lang_sync(en_us, paths)
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(bundle.has_message("menu-open"));
+    assert!(!bundle.has_message("menu-file"));
+}
+
+#[test]
+fn test_locale_aliases() {
+    let es: LanguageIdentifier = "es".parse().unwrap();
+    let es_es: LanguageIdentifier = "es-ES".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("browser/es-ES/menu.ftl", "menu-file = Archivo");
+
+    let mut options = FileSourceOptions::default();
+    options.aliases.insert(es.clone(), es_es.clone());
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::new(vec![es.clone()]));
+    reg.register_sources(vec![FileSourceBuilder::new(
+        "browser",
+        vec![es_es.clone()],
+        "browser/{locale}/",
+    )
+    .options(options)
+    .build(fetcher)
+    .unwrap()])
+        .unwrap();
+
+    let mut available = reg.get_available_locales().unwrap();
+    available.sort_by_key(|locale| locale.to_string());
+    assert_eq!(available, vec![es.clone(), es_es.clone()]);
+
+    let bundle = reg
+        .generate_bundles_for_lang_sync(es.clone(), vec!["menu.ftl".into()])
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(bundle.has_message("menu-file"));
+    // The bundle records the locale whose files it was built from.
+    assert_eq!(bundle.locales, vec![es, es_es]);
+}
+
+/// Wraps a `MemoryFileFetcher`, recording the size of each batch.
+#[derive(Clone, Default)]
+struct BatchingFileFetcher {
+    inner: MemoryFileFetcher,
+    batches: Rc<RefCell<Vec<usize>>>,
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for BatchingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch(resource_id).await
+    }
+
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        self.batches.borrow_mut().push(resource_ids.len());
+        resource_ids
+            .iter()
+            .map(|id| self.inner.fetch_bytes_sync(id))
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn test_generate_bundles_batches_fetches() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = BatchingFileFetcher::default();
+    fetcher
+        .inner
+        .insert("browser/en-US/menu.ftl", "menu-file = File");
+    fetcher
+        .inner
+        .insert("browser/en-US/edit.ftl", "menu-edit = Edit");
+    fetcher
+        .inner
+        .insert("toolkit/en-US/menu.ftl", "menu-file = File");
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::new(vec![en_us.clone()]));
+    reg.register_sources(vec![
+        FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+            .build(fetcher.clone())
+            .unwrap(),
+        FileSourceBuilder::new("browser", vec![en_us.clone()], "browser/{locale}/")
+            .build(fetcher.clone())
+            .unwrap(),
+    ])
+    .unwrap();
+
+    let paths = vec!["menu.ftl".into(), "edit.ftl".into()];
+    let mut i = reg.generate_bundles_for_lang(en_us, paths);
+
+    let bundle = i.next().await.unwrap().ok().unwrap();
+    assert!(bundle.has_message("menu-edit"));
+    assert_eq!(*fetcher.batches.borrow(), vec![2]);
+}
+
+/// Wraps a `MemoryFileFetcher`, yielding to the executor once before each
+/// async fetch.
+#[derive(Clone, Default)]
+struct YieldingFileFetcher {
+    inner: MemoryFileFetcher,
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for YieldingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        tokio::task::yield_now().await;
+        self.inner.fetch(resource_id).await
+    }
+}
+
+#[tokio::test]
+async fn test_drop_generate_bundles_mid_load() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = YieldingFileFetcher::default();
+    fetcher
+        .inner
+        .insert("browser/en-US/menu.ftl", "menu-file = File");
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(env.clone());
+    reg.register_sources(vec![FileSourceBuilder::new(
+        "browser",
+        vec![en_us.clone()],
+        "browser/{locale}/",
+    )
+    .build(fetcher)
+    .unwrap()])
+        .unwrap();
+
+    let paths: Vec<ResourceId> = vec!["menu.ftl".into()];
+    let mut bundles = reg.generate_bundles_for_lang(en_us.clone(), paths.clone());
+    assert!(futures::poll!(bundles.next()).is_pending());
+    drop(bundles);
+
+    {
+        let lock = reg.lock();
+        let source = lock.get_source(0, "browser").unwrap();
+        assert_eq!(source.has_file(&en_us, &paths[0]), None);
+    }
+
+    let bundle = reg
+        .generate_bundles_for_lang_sync(en_us.clone(), paths.clone())
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(bundle.has_message("menu-file"));
+
+    let mut bundles = reg.generate_bundles_for_lang(en_us, paths);
+    assert!(bundles.next().await.is_some());
+    assert!(env.errors().is_empty());
+}
+
+/// A fetcher whose async fetches never complete.
+struct HangingFileFetcher;
+
+#[async_trait(?Send)]
+impl FileFetcher for HangingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            resource_id.value.clone(),
+        ))
+    }
+
+    async fn fetch(&self, _resource_id: &ResourceId) -> io::Result<String> {
+        futures::future::pending().await
+    }
+}
+
+/// A timer whose delays elapse immediately.
+struct ImmediateTimer;
+
+impl Timer for ImmediateTimer {
+    fn sleep(&self, _duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(futures::future::ready(()))
+    }
+}
+
+#[tokio::test]
+async fn test_generate_bundles_after_timeout() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("app/en-US/menu.ftl", "menu-file = File");
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(env.clone());
+    reg.register_sources(vec![
+        FileSourceBuilder::new("app", vec![en_us.clone()], "app/{locale}/")
+            .build(fetcher)
+            .unwrap(),
+        FileSourceBuilder::new("langpack", vec![en_us.clone()], "langpack/{locale}/")
+            .options(FileSourceOptions {
+                fetch_timeout: Some(Duration::from_secs(5)),
+                ..Default::default()
+            })
+            .timer(ImmediateTimer)
+            .build(HangingFileFetcher)
+            .unwrap(),
+    ])
+    .unwrap();
+
+    let mut bundles = reg.generate_bundles_for_lang(en_us, vec!["menu.ftl".into()]);
+    let bundle = bundles.next().await.unwrap().ok().unwrap();
+    assert!(bundle.has_message("menu-file"));
+    assert!(bundles.next().await.is_none());
+}
+
+#[test]
+fn test_register_sources_without_timer() {
+    use l10nregistry::errors::L10nRegistrySetupError;
+    use l10nregistry::source::FileSource;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let options = FileSourceOptions {
+        fetch_timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+    let source = FileSource::try_new(
+        "langpack".to_string(),
+        None,
+        vec![en_us.clone()],
+        "langpack/{locale}/".to_string(),
+        options.clone(),
+        MemoryFileFetcher::new(),
+    )
+    .unwrap();
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::new(vec![en_us.clone()]));
+    let missing_timer = Err(L10nRegistrySetupError::MissingTimer {
+        name: "langpack".to_string(),
+    });
+    assert_eq!(reg.register_sources(vec![source.clone()]), missing_timer);
+    assert!(reg.get_source("langpack").unwrap().is_none());
+
+    // Nor can a source with a timer be replaced by one without.
+    reg.register_sources(vec![FileSourceBuilder::new(
+        "langpack",
+        vec![en_us],
+        "langpack/{locale}/",
+    )
+    .options(options)
+    .timer(ImmediateTimer)
+    .build(MemoryFileFetcher::new())
+    .unwrap()])
+        .unwrap();
+    assert_eq!(reg.update_sources(vec![source]), missing_timer);
+}
+
+fn get_preload_registry(
+    fetcher: &MemoryFileFetcher,
+) -> L10nRegistry<TestEnvironment, MockBundleAdapter> {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    fetcher.insert("toolkit/en-US/menu.ftl", "menu-file = File");
+    fetcher.insert("toolkit/pl/menu.ftl", "menu-file = Plik");
+    fetcher.insert("browser/en-US/menu.ftl", "menu-file = File");
+    fetcher.insert("browser/en-US/brand.ftl", "brand-name = Firefox");
+
+    let reg = L10nRegistry::with_provider(TestEnvironment::new(vec![en_us.clone()]));
+    reg.register_sources(vec![
+        FileSourceBuilder::new("toolkit", vec![en_us.clone(), pl], "toolkit/{locale}/")
+            .build(fetcher.clone())
+            .unwrap(),
+        FileSourceBuilder::new("browser", vec![en_us], "browser/{locale}/")
+            .build(fetcher.clone())
+            .unwrap(),
+    ])
+    .unwrap();
+    reg
+}
+
+fn assert_preload_report(report: &PreloadReport) {
+    let paths = |entries: &[(LanguageIdentifier, ResourceId)]| -> Vec<String> {
+        entries
+            .iter()
+            .map(|(locale, resource_id)| format!("{}/{}", locale, resource_id.value))
+            .collect()
+    };
+
+    let toolkit = report.get_source("toolkit").unwrap();
+    assert_eq!(
+        paths(&toolkit.loaded),
+        vec!["en-US/menu.ftl", "pl/menu.ftl"]
+    );
+    assert_eq!(
+        paths(&toolkit.missing),
+        vec!["en-US/brand.ftl", "pl/brand.ftl"]
+    );
+    assert!(toolkit.failed.is_empty());
+
+    // The browser source doesn't provide `pl`, so it is left out.
+    let browser = report.get_source("browser").unwrap();
+    assert_eq!(
+        paths(&browser.loaded),
+        vec!["en-US/menu.ftl", "en-US/brand.ftl"]
+    );
+    assert!(browser.missing.is_empty());
+}
+
+#[test]
+fn test_preload_sync() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    let reg = get_preload_registry(&fetcher);
+
+    let report = reg.preload_sync(
+        &[en_us, pl.clone()],
+        &["menu.ftl".into(), "brand.ftl".into()],
+    );
+    assert_preload_report(&report);
+
+    // Bundles are generated from the preloaded resources.
+    fetcher.clear();
+    let bundle = reg
+        .generate_bundles_for_lang_sync(pl, vec!["menu.ftl".into()])
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(bundle.has_message("menu-file"));
+}
+
+#[tokio::test]
+async fn test_preload() {
+    use futures::stream::StreamExt;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    let reg = get_preload_registry(&fetcher);
+
+    let report = reg
+        .preload(
+            &[en_us.clone(), pl],
+            &["menu.ftl".into(), "brand.ftl".into()],
+        )
+        .await;
+    assert_preload_report(&report);
+
+    fetcher.clear();
+    let bundle = reg
+        .generate_bundles_for_lang(en_us, vec!["menu.ftl".into(), "brand.ftl".into()])
+        .next()
+        .await
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert!(bundle.has_message("brand-name"));
+}
+
+#[test]
+fn test_stats() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    let reg = get_preload_registry(&fetcher);
+
+    reg.preload_sync(
+        &[en_us, pl.clone()],
+        &["menu.ftl".into(), "brand.ftl".into()],
+    );
+    let stats = reg.stats();
+    assert_eq!(stats.fetches, 6);
+    assert_eq!(stats.cache_misses, 6);
+    assert_eq!(stats.cache_hits, 0);
+
+    let toolkit = reg.get_source("toolkit").unwrap().unwrap();
+    let browser = reg.get_source("browser").unwrap().unwrap();
+    assert_eq!(stats, toolkit.stats() + browser.stats());
+
+    reg.generate_bundles_for_lang_sync(pl, vec!["menu.ftl".into()])
+        .next()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(reg.stats().fetches, 6);
+    assert!(reg.stats().cache_hits > 0);
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn test_signed_manifest() {
+    use ed25519_dalek::{Signer, SigningKey};
+    use l10nregistry::errors::L10nRegistrySetupError;
+    use l10nregistry::source::ChecksumManifest;
+
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("langpack/pl/menu.ftl", "menu-file = Plik");
+    fetcher.insert("langpack/pl/brand.ftl", "brand-name = Firefox");
+
+    let mut checksums = ChecksumManifest::new();
+    checksums.insert_contents("langpack/pl/menu.ftl", b"menu-file = Plik");
+    let mut manifest =
+        b"name: langpack-pl\nmetasource: langpack\nlocales: pl\npre_path: langpack/{locale}/\n\n"
+            .to_vec();
+    checksums.write(&mut manifest).unwrap();
+
+    let trusted = SigningKey::from_bytes(&[7; 32]);
+    let untrusted = SigningKey::from_bytes(&[8; 32]);
+    let signature = trusted.sign(&manifest).to_bytes();
+
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::new(vec![pl.clone()]));
+    assert_eq!(
+        reg.verify_manifest(&manifest, &signature),
+        Err(L10nRegistrySetupError::InvalidSignature)
+    );
+
+    reg.add_trusted_key(trusted.verifying_key().to_bytes())
+        .unwrap();
+    let source = reg.verify_manifest(&manifest, &signature).unwrap();
+    assert_eq!(source.name(), "langpack-pl");
+    assert_eq!(source.metasource(), Some("langpack"));
+    assert_eq!(source.locales(), std::slice::from_ref(&pl));
+    assert_eq!(source.pre_path(), "langpack/{locale}/");
+    assert_eq!(source.checksums(), &checksums);
+
+    // Tampered manifests, other keys and malformed signatures are rejected.
+    let mut tampered = manifest.clone();
+    tampered[6] = b'x';
+    assert_eq!(
+        reg.verify_manifest(&tampered, &signature),
+        Err(L10nRegistrySetupError::InvalidSignature)
+    );
+    assert_eq!(
+        reg.verify_manifest(&manifest, &untrusted.sign(&manifest).to_bytes()),
+        Err(L10nRegistrySetupError::InvalidSignature)
+    );
+    assert_eq!(
+        reg.verify_manifest(&manifest, &signature[1..]),
+        Err(L10nRegistrySetupError::InvalidSignature)
+    );
+
+    // Manifests with Windows line endings are read the same way.
+    let crlf = String::from_utf8(manifest.clone())
+        .unwrap()
+        .replace('\n', "\r\n")
+        .into_bytes();
+    let crlf_source = reg
+        .verify_manifest(&crlf, &trusted.sign(&crlf).to_bytes())
+        .unwrap();
+    assert_eq!(crlf_source.pre_path(), "langpack/{locale}/");
+    assert_eq!(crlf_source.checksums(), &checksums);
+
+    // Bytes which aren't a public key can't be trusted.
+    assert_eq!(
+        reg.add_trusted_key([2; 32]),
+        Err(L10nRegistrySetupError::InvalidKey)
+    );
+
+    // A trusted signature doesn't make up for a malformed manifest.
+    let malformed = b"name: langpack-pl\nlocales: pl\n";
+    assert!(matches!(
+        reg.verify_manifest(malformed, &trusted.sign(malformed).to_bytes()),
+        Err(L10nRegistrySetupError::InvalidManifest { .. })
+    ));
+
+    // The registered source only serves the files listed in the manifest,
+    // even if its options are set again.
+    reg.register_sources(vec![source
+        .into_builder()
+        .options(FileSourceOptions::default())
+        .build(fetcher.clone())
+        .unwrap()])
+        .unwrap();
+    let langpack = reg.get_source("langpack-pl").unwrap().unwrap();
+    assert!(langpack
+        .fetch_file_sync(&pl, &"menu.ftl".into(), false)
+        .is_some());
+    assert!(langpack
+        .fetch_file_sync(&pl, &"brand.ftl".into(), false)
+        .is_required_and_missing());
+
+    // Sources can also be verified and registered in one step.
+    let reg: L10nRegistry<TestEnvironment, MockBundleAdapter> =
+        L10nRegistry::with_provider(TestEnvironment::new(vec![pl.clone()]));
+    reg.add_trusted_key(trusted.verifying_key().to_bytes())
+        .unwrap();
+    assert_eq!(
+        reg.register_signed_source(&tampered, &signature, fetcher.clone()),
+        Err(L10nRegistrySetupError::InvalidSignature)
+    );
+    assert!(reg.get_source("langpack-pl").unwrap().is_none());
+    reg.register_signed_source(&manifest, &signature, fetcher)
+        .unwrap();
+    let langpack = reg.get_source("langpack-pl").unwrap().unwrap();
+    assert!(langpack
+        .fetch_file_sync(&pl, &"brand.ftl".into(), false)
+        .is_required_and_missing());
+}
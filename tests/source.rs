@@ -1,6 +1,21 @@
-use fluent_fallback::types::{ResourceType, ToResourceId};
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use fluent_fallback::types::{ResourceId, ResourceType, ToResourceId};
 use futures::future::join_all;
-use l10nregistry::testing::TestFileFetcher;
+use l10nregistry::env::Timer;
+use l10nregistry::errors::{L10nRegistryError, L10nRegistrySetupError};
+use l10nregistry::fetchers::MemoryFileFetcher;
+use l10nregistry::source::{
+    FileFetcher, FileSource, FileSourceBuilder, FileSourceOptions, ResourceOption, RetryPolicy,
+    SourceStats,
+};
+use l10nregistry::testing::{TestEnvironment, TestFileFetcher};
 use unic_langid::LanguageIdentifier;
 
 static FTL_RESOURCE_PRESENT: &str = "toolkit/global/textActions.ftl";
@@ -303,3 +318,783 @@ fn test_indexed() {
         .is_none());
     assert_eq!(fs1.has_file(&en_us, &path_missing.into()), Some(false));
 }
+
+/// Wraps a `TestFileFetcher`, counting the calls to each of the fetch methods
+/// and recording the size of each batch.
+#[derive(Clone, Default)]
+struct CountingFileFetcher {
+    inner: TestFileFetcher,
+    sync_fetches: Rc<Cell<usize>>,
+    async_fetches: Rc<Cell<usize>>,
+    batches: Rc<RefCell<Vec<usize>>>,
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for CountingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.sync_fetches.set(self.sync_fetches.get() + 1);
+        self.inner.fetch_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.async_fetches.set(self.async_fetches.get() + 1);
+        self.inner.fetch(resource_id).await
+    }
+
+    async fn fetch_many(&self, resource_ids: &[ResourceId]) -> Vec<io::Result<Vec<u8>>> {
+        self.batches.borrow_mut().push(resource_ids.len());
+        join_all(resource_ids.iter().map(|id| self.fetch_bytes(id))).await
+    }
+}
+
+fn get_resource(option: ResourceOption) -> l10nregistry::source::RcResource {
+    match option {
+        ResourceOption::Some(res) => res,
+        _ => panic!("Expected a resource"),
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_sync_resolves_pending_async() {
+    let fetcher = CountingFileFetcher::default();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .build(fetcher.clone())
+        .unwrap();
+
+    let pending = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into());
+    let pending_2 = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into());
+    let sync = get_resource(fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), true));
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()),
+        Some(true)
+    );
+
+    let res = get_resource(pending.await);
+    let res_2 = get_resource(pending_2.await);
+    assert!(Rc::ptr_eq(&sync, &res));
+    assert!(Rc::ptr_eq(&sync, &res_2));
+
+    assert_eq!(fetcher.sync_fetches.get(), 1);
+    assert_eq!(fetcher.async_fetches.get(), 0);
+
+    // The cache holds the same resource too.
+    let cached = get_resource(fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false));
+    assert!(Rc::ptr_eq(&sync, &cached));
+    assert_eq!(fetcher.sync_fetches.get(), 1);
+}
+
+#[tokio::test]
+async fn test_fetch_sync_resolves_pending_async_missing() {
+    let fetcher = CountingFileFetcher::default();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .build(fetcher.clone())
+        .unwrap();
+
+    let pending = fs1.fetch_file(&en_us, &FTL_RESOURCE_MISSING.into());
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), true)
+        .is_required_and_missing());
+    assert!(pending.await.is_required_and_missing());
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()),
+        Some(false)
+    );
+
+    assert_eq!(fetcher.sync_fetches.get(), 1);
+    assert_eq!(fetcher.async_fetches.get(), 0);
+}
+
+#[tokio::test]
+async fn test_fetch_sync_without_overload_while_pending() {
+    let fetcher = CountingFileFetcher::default();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .reporter(env.clone())
+        .build(fetcher.clone())
+        .unwrap();
+
+    let pending = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into());
+    let file = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false);
+    assert!(file.is_pending());
+    assert!(!file.is_some());
+    assert!(!file.is_none());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::PendingAsyncLoad {
+            locale: en_us.clone(),
+            resource_id: FTL_RESOURCE_PRESENT.into(),
+        }]
+    );
+    assert_eq!(fetcher.sync_fetches.get(), 0);
+
+    // The returned future resolves together with the pending load.
+    let blocked = match file {
+        ResourceOption::Pending(future) => get_resource(future.await),
+        _ => unreachable!(),
+    };
+    assert!(Rc::ptr_eq(&blocked, &get_resource(pending.await)));
+    assert_eq!(fetcher.async_fetches.get(), 1);
+}
+
+/// Fails to fetch any resource with `io::ErrorKind::PermissionDenied`.
+struct FailingFileFetcher;
+
+#[async_trait(?Send)]
+impl FileFetcher for FailingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            resource_id.value.clone(),
+        ))
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.fetch_sync(resource_id)
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_error() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .reporter(env.clone())
+        .build(FailingFileFetcher)
+        .unwrap();
+
+    let file = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false);
+    assert!(file.is_fetch_failed());
+    assert!(file.is_none());
+    assert!(file.is_required_and_missing());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::FetchError {
+            resource_id: "toolkit/en-US/toolkit/global/textActions.ftl".into(),
+            locale: en_us.clone(),
+            kind: io::ErrorKind::PermissionDenied,
+            message: "toolkit/en-US/toolkit/global/textActions.ftl".to_string(),
+            origin: None,
+        }]
+    );
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()),
+        Some(false)
+    );
+
+    let file = fs1
+        .fetch_file(
+            &en_us,
+            &FTL_RESOURCE_MISSING.to_resource_id(ResourceType::Optional),
+        )
+        .await;
+    assert!(file.is_fetch_failed());
+    assert!(file.is_none());
+    assert!(!file.is_required_and_missing());
+    assert_eq!(env.errors().len(), 2);
+}
+
+#[tokio::test]
+async fn test_fetch_error_is_cached() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .reporter(env.clone())
+        .build(FailingFileFetcher)
+        .unwrap();
+
+    let file = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false);
+    assert!(file.is_fetch_failed());
+
+    // Later requests get the cached failure instead of a plain miss, without
+    // fetching or reporting it again.
+    let file = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false);
+    assert!(file.is_fetch_failed());
+    assert!(file.is_required_and_missing());
+    let file = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into()).await;
+    assert!(file.is_fetch_failed());
+    let files = fs1.fetch_files(&en_us, &[FTL_RESOURCE_PRESENT.into()]);
+    assert!(files.into_iter().next().unwrap().await.is_fetch_failed());
+
+    // The cached failure matches the type of the later request.
+    let file = fs1.fetch_file_sync(
+        &en_us,
+        &FTL_RESOURCE_PRESENT.to_resource_id(ResourceType::Optional),
+        false,
+    );
+    assert!(file.is_fetch_failed());
+    assert!(!file.is_required_and_missing());
+
+    assert_eq!(env.errors().len(), 1);
+}
+
+#[test]
+fn test_fetch_not_found_is_not_an_error() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .reporter(env.clone())
+        .build(TestFileFetcher::new())
+        .unwrap();
+
+    let file = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), false);
+    assert!(file.is_required_and_missing());
+    assert!(!file.is_fetch_failed());
+    assert!(env.errors().is_empty());
+}
+
+#[test]
+fn test_pre_path_template() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let mut options = FileSourceOptions::default();
+    options
+        .variables
+        .insert("channel".to_string(), "toolkit".to_string());
+
+    let fs1 = FileSourceBuilder::new(
+        "toolkit",
+        vec![en_us.clone()],
+        "{channel}/{language}-{region}/",
+    )
+    .options(options)
+    .build(TestFileFetcher::new())
+    .unwrap();
+    assert_eq!(fs1.pre_path(), "{channel}/{language}-{region}/");
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()), None);
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false)
+        .is_some());
+
+    let fs2 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "{platform}/{locale}/")
+        .build(TestFileFetcher::new());
+    let expected = L10nRegistrySetupError::UnknownPlaceholder {
+        pre_path: "{platform}/{locale}/".to_string(),
+        placeholder: "platform".to_string(),
+    };
+    assert_eq!(fs2.err(), Some(expected.clone()));
+
+    let fs3 = FileSource::try_new(
+        "toolkit".to_string(),
+        None,
+        vec![en_us],
+        "{platform}/{locale}/".to_string(),
+        FileSourceOptions::default(),
+        TestFileFetcher::new(),
+    );
+    assert_eq!(fs3.err(), Some(expected));
+}
+
+#[test]
+fn test_locale_aliases() {
+    let en: LanguageIdentifier = "en".parse().unwrap();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let en_gb: LanguageIdentifier = "en-GB".parse().unwrap();
+    let de: LanguageIdentifier = "de".parse().unwrap();
+    let env = TestEnvironment::new(vec![en.clone()]);
+
+    let mut options = FileSourceOptions::default();
+    options.aliases.insert(en.clone(), en_us.clone());
+    options.aliases.insert(de.clone(), en_gb.clone());
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .options(options)
+        .reporter(env.clone())
+        .build(FailingFileFetcher)
+        .unwrap();
+    assert_eq!(fs1.resolve_locale(&en), Some(&en_us));
+    assert_eq!(fs1.resolve_locale(&de), None);
+    assert_eq!(fs1.has_file(&de, &FTL_RESOURCE_PRESENT.into()), Some(false));
+
+    let file = fs1.fetch_file_sync(&en, &FTL_RESOURCE_PRESENT.into(), false);
+    assert!(file.is_fetch_failed());
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::FetchError {
+            resource_id: "toolkit/en-US/toolkit/global/textActions.ftl".into(),
+            locale: en_us.clone(),
+            kind: io::ErrorKind::PermissionDenied,
+            message: "toolkit/en-US/toolkit/global/textActions.ftl".to_string(),
+            origin: None,
+        }]
+    );
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()),
+        Some(false)
+    );
+}
+
+#[tokio::test]
+async fn test_locale_aliases_async() {
+    let en: LanguageIdentifier = "en".parse().unwrap();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+
+    let mut options = FileSourceOptions::default();
+    options.aliases.insert(en.clone(), en_us.clone());
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .options(options)
+        .build(TestFileFetcher::new())
+        .unwrap();
+
+    assert_eq!(fs1.has_file(&en, &FTL_RESOURCE_PRESENT.into()), None);
+    assert!(fs1
+        .fetch_file(&en, &FTL_RESOURCE_PRESENT.into())
+        .await
+        .is_some());
+    assert_eq!(fs1.has_file(&en, &FTL_RESOURCE_PRESENT.into()), Some(true));
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()),
+        Some(true)
+    );
+    assert!(fs1
+        .fetch_file_sync(&en, &FTL_RESOURCE_MISSING.into(), false)
+        .is_required_and_missing());
+}
+
+#[tokio::test]
+async fn test_fetch_files() {
+    let fetcher = CountingFileFetcher::default();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let history = "toolkit/updates/history.ftl";
+    let features = "toolkit/featuregates/features.ftl";
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .build(fetcher.clone())
+        .unwrap();
+
+    let cached = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into()).await;
+    assert!(cached.is_some());
+    assert_eq!(fetcher.async_fetches.get(), 1);
+
+    let statuses = fs1.fetch_files(
+        &en_us,
+        &[
+            FTL_RESOURCE_PRESENT.into(),
+            history.into(),
+            FTL_RESOURCE_MISSING.into(),
+            features.into(),
+            history.into(),
+        ],
+    );
+    assert_eq!(statuses.len(), 5);
+    let files = join_all(statuses).await;
+    assert!(files[0].is_some());
+    assert!(files[1].is_some());
+    assert!(files[2].is_required_and_missing());
+    assert!(files[3].is_some());
+    assert!(files[4].is_some());
+
+    assert_eq!(*fetcher.batches.borrow(), vec![3]);
+    assert_eq!(fetcher.async_fetches.get(), 4);
+    assert_eq!(fs1.has_file(&en_us, &features.into()), Some(true));
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()),
+        Some(false)
+    );
+
+    // Only a single resource left to fetch, no batch needed.
+    let statuses = fs1.fetch_files(
+        &en_us,
+        &[history.into(), "toolkit/printing/printUI.ftl".into()],
+    );
+    assert!(join_all(statuses).await.iter().all(|file| file.is_some()));
+    assert_eq!(*fetcher.batches.borrow(), vec![3]);
+    assert_eq!(fetcher.async_fetches.get(), 5);
+}
+
+/// Wraps a `CountingFileFetcher`, yielding to the executor once before
+/// returning the result of each async fetch, so that loads can be dropped
+/// while in flight.
+#[derive(Clone, Default)]
+struct YieldingFileFetcher {
+    inner: CountingFileFetcher,
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for YieldingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        let source = self.inner.fetch(resource_id).await;
+        tokio::task::yield_now().await;
+        source
+    }
+}
+
+#[tokio::test]
+async fn test_drop_mid_load() {
+    let fetcher = YieldingFileFetcher::default();
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .reporter(env.clone())
+        .build(fetcher.clone())
+        .unwrap();
+
+    let mut pending = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into());
+    let pending_2 = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into());
+    assert!(futures::poll!(&mut pending).is_pending());
+    assert_eq!(fetcher.inner.async_fetches.get(), 1);
+
+    // The load continues as long as anybody waits for it.
+    drop(pending);
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false)
+        .is_pending());
+    assert_eq!(env.errors().len(), 1);
+
+    // Dropping the last future cancels the load.
+    drop(pending_2);
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()), None);
+
+    let file = fs1.fetch_file_sync(&en_us, &FTL_RESOURCE_PRESENT.into(), false);
+    assert!(file.is_some());
+    assert_eq!(env.errors().len(), 1);
+    assert_eq!(fetcher.inner.sync_fetches.get(), 1);
+
+    // A load dropped before being polled never starts, and is refetched.
+    drop(fs1.fetch_file(&en_us, &FTL_RESOURCE_MISSING.into()));
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()), None);
+    let file = fs1.fetch_file(&en_us, &FTL_RESOURCE_MISSING.into()).await;
+    assert!(file.is_required_and_missing());
+    assert_eq!(fetcher.inner.async_fetches.get(), 2);
+    assert_eq!(
+        fs1.has_file(&en_us, &FTL_RESOURCE_MISSING.into()),
+        Some(false)
+    );
+}
+
+/// Wraps a `TestFileFetcher`, never completing the first `hangs` async
+/// fetches.
+#[derive(Clone, Default)]
+struct HangingFileFetcher {
+    inner: TestFileFetcher,
+    hangs: Rc<Cell<usize>>,
+}
+
+#[async_trait(?Send)]
+impl FileFetcher for HangingFileFetcher {
+    fn fetch_sync(&self, resource_id: &ResourceId) -> io::Result<String> {
+        self.inner.fetch_sync(resource_id)
+    }
+
+    async fn fetch(&self, resource_id: &ResourceId) -> io::Result<String> {
+        if self.hangs.get() > 0 {
+            self.hangs.set(self.hangs.get() - 1);
+            futures::future::pending::<()>().await;
+        }
+        self.inner.fetch(resource_id).await
+    }
+}
+
+/// A timer whose delays elapse immediately, recording their durations.
+#[derive(Clone, Default)]
+struct TestTimer {
+    sleeps: Rc<RefCell<Vec<Duration>>>,
+}
+
+impl Timer for TestTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        self.sleeps.borrow_mut().push(duration);
+        Box::pin(futures::future::ready(()))
+    }
+}
+
+#[tokio::test]
+async fn test_fetch_timeout() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = HangingFileFetcher::default();
+    fetcher.hangs.set(1);
+    let timer = TestTimer::default();
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .options(FileSourceOptions {
+            fetch_timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        })
+        .reporter(env.clone())
+        .timer(timer.clone())
+        .build(fetcher)
+        .unwrap();
+
+    let file = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into()).await;
+    assert!(file.is_fetch_failed());
+    assert!(file.is_required_and_missing());
+    assert_eq!(*timer.sleeps.borrow(), vec![Duration::from_secs(1)]);
+    assert_eq!(
+        env.errors(),
+        vec![L10nRegistryError::FetchError {
+            resource_id: "toolkit/en-US/toolkit/global/textActions.ftl".into(),
+            locale: en_us.clone(),
+            kind: io::ErrorKind::TimedOut,
+            message: "Fetch timed out after 1s".to_string(),
+            origin: None,
+        }]
+    );
+
+    // The timeout is not cached, so the next request fetches it again.
+    assert_eq!(fs1.has_file(&en_us, &FTL_RESOURCE_PRESENT.into()), None);
+    let file = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into()).await;
+    assert!(file.is_some());
+    assert_eq!(env.errors().len(), 1);
+
+    // A timeout can't be enforced without a timer.
+    let fs2 = FileSourceBuilder::new("toolkit", vec![en_us], "toolkit/{locale}/")
+        .options(FileSourceOptions {
+            fetch_timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        })
+        .build(TestFileFetcher::new());
+    assert_eq!(
+        fs2.err(),
+        Some(L10nRegistrySetupError::MissingTimer {
+            name: "toolkit".to_string()
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_fetch_retry() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = HangingFileFetcher::default();
+    fetcher.hangs.set(2);
+    let timer = TestTimer::default();
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .options(FileSourceOptions {
+            fetch_timeout: Some(Duration::from_secs(1)),
+            retry_policy: RetryPolicy {
+                max_retries: 2,
+                backoff: Duration::from_millis(10),
+            },
+            ..Default::default()
+        })
+        .reporter(env.clone())
+        .timer(timer.clone())
+        .build(fetcher.clone())
+        .unwrap();
+
+    let file = fs1.fetch_file(&en_us, &FTL_RESOURCE_PRESENT.into()).await;
+    assert!(file.is_some());
+    assert_eq!(
+        *timer.sleeps.borrow(),
+        vec![
+            Duration::from_secs(1),
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+            Duration::from_millis(20),
+            Duration::from_secs(1),
+        ]
+    );
+    assert!(env.errors().is_empty());
+
+    // Missing resources are not retried.
+    timer.sleeps.borrow_mut().clear();
+    let file = fs1.fetch_file(&en_us, &FTL_RESOURCE_MISSING.into()).await;
+    assert!(file.is_required_and_missing());
+    assert_eq!(*timer.sleeps.borrow(), vec![Duration::from_secs(1)]);
+
+    // Failed batches are retried for the resources which failed.
+    fetcher.hangs.set(1);
+    timer.sleeps.borrow_mut().clear();
+    let statuses = fs1.fetch_files(
+        &en_us,
+        &[
+            "toolkit/updates/history.ftl".into(),
+            "toolkit/featuregates/features.ftl".into(),
+        ],
+    );
+    assert!(join_all(statuses).await.iter().all(|file| file.is_some()));
+    assert_eq!(timer.sleeps.borrow().len(), 3);
+    assert!(env.errors().is_empty());
+}
+
+#[tokio::test]
+async fn test_stats() {
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("toolkit/en-US/menu.ftl", "menu-file = File\n");
+    fetcher.insert("toolkit/en-US/edit.ftl", "menu-edit = Edit\n");
+    fetcher.insert("toolkit/en-US/broken.ftl", "menu-file = File\n!!!\n");
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .build(fetcher)
+        .unwrap();
+    assert_eq!(fs1.stats(), SourceStats::default());
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"menu.ftl".into(), false)
+        .is_some());
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"menu.ftl".into(), false)
+        .is_some());
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"broken.ftl".into(), false)
+        .is_some());
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), false)
+        .is_none());
+    // A cached miss is a hit too, while a locale the source doesn't serve
+    // never reaches the cache.
+    assert!(fs1
+        .fetch_file_sync(&en_us, &FTL_RESOURCE_MISSING.into(), false)
+        .is_none());
+    let pl: LanguageIdentifier = "pl".parse().unwrap();
+    assert!(fs1
+        .fetch_file_sync(&pl, &"menu.ftl".into(), false)
+        .is_none());
+
+    let stats = fs1.stats();
+    assert_eq!(stats.cache_hits, 2);
+    assert_eq!(stats.cache_misses, 3);
+    assert_eq!(stats.fetches, 3);
+    assert_eq!(stats.parse_errors, 1);
+    assert_eq!(stats.bytes_loaded, 17 + 21);
+
+    // A batch counts as one fetch per resource, and clones share the counters.
+    let statuses = fs1.clone().fetch_files(
+        &en_us,
+        &["menu.ftl".into(), "edit.ftl".into(), "gone.ftl".into()],
+    );
+    join_all(statuses).await;
+
+    let stats = fs1.stats();
+    assert_eq!(stats.cache_hits, 3);
+    assert_eq!(stats.cache_misses, 5);
+    assert_eq!(stats.fetches, 5);
+    assert_eq!(stats.bytes_loaded, 17 + 21 + 17);
+}
+
+#[cfg(not(feature = "checksums"))]
+#[test]
+fn test_checksums_unsupported() {
+    use l10nregistry::source::ChecksumManifest;
+    use std::sync::Arc;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let options = FileSourceOptions {
+        checksums: Some(Arc::new(ChecksumManifest::new())),
+        ..Default::default()
+    };
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us], "toolkit/{locale}/")
+        .options(options)
+        .build(TestFileFetcher::new());
+    assert_eq!(
+        fs1.err(),
+        Some(L10nRegistrySetupError::UnsupportedOption {
+            name: "toolkit".to_string(),
+            option: "checksums".to_string(),
+        })
+    );
+}
+
+#[cfg(feature = "checksums")]
+#[tokio::test]
+async fn test_checksums() {
+    use l10nregistry::source::ChecksumManifest;
+    use std::sync::Arc;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("toolkit/en-US/menu.ftl", "menu-file = File\n");
+    fetcher.insert("toolkit/en-US/edit.ftl", "menu-edit = Edit\n");
+    fetcher.insert("toolkit/en-US/extra.ftl", "menu-extra = Extra\n");
+
+    let mut checksums = ChecksumManifest::new();
+    checksums.insert_contents("toolkit/en-US/menu.ftl", b"menu-file = File\n");
+    checksums.insert_contents("toolkit/en-US/edit.ftl", b"menu-edit = Edit!\n");
+    let options = FileSourceOptions {
+        checksums: Some(Arc::new(checksums)),
+        ..Default::default()
+    };
+
+    let fs1 = FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+        .options(options)
+        .reporter(env.clone())
+        .build(fetcher)
+        .unwrap();
+
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"menu.ftl".into(), false)
+        .is_some());
+    assert!(env.errors().is_empty());
+
+    let file = fs1.fetch_file_sync(&en_us, &"edit.ftl".into(), false);
+    assert!(file.is_required_and_missing());
+    assert!(!file.is_fetch_failed());
+    match &env.errors()[..] {
+        [L10nRegistryError::ChecksumMismatch {
+            resource_id,
+            expected: Some(expected),
+            actual,
+            ..
+        }] => {
+            assert_eq!(resource_id.value, "toolkit/en-US/edit.ftl");
+            assert_ne!(expected, actual);
+        }
+        errors => panic!("Unexpected errors: {:?}", errors),
+    }
+
+    // Files missing from the manifest are rejected as well.
+    assert!(fs1
+        .fetch_file(&en_us, &"extra.ftl".into())
+        .await
+        .is_required_and_missing());
+    assert!(matches!(
+        env.errors().last(),
+        Some(L10nRegistryError::ChecksumMismatch { expected: None, .. })
+    ));
+}
+
+#[cfg(feature = "checksums")]
+#[test]
+fn test_checksums_startup_cache_bom() {
+    use l10nregistry::source::{ChecksumManifest, StartupCache};
+    use std::sync::Arc;
+
+    let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+    let env = TestEnvironment::new(vec![en_us.clone()]);
+    let fetcher = MemoryFileFetcher::new();
+    fetcher.insert("toolkit/en-US/menu.ftl", "\u{FEFF}menu-file = File\n");
+
+    let mut checksums = ChecksumManifest::new();
+    checksums.insert_contents("toolkit/en-US/menu.ftl", b"\xEF\xBB\xBFmenu-file = File\n");
+    let options = FileSourceOptions {
+        checksums: Some(Arc::new(checksums)),
+        ..Default::default()
+    };
+    let build = || {
+        FileSourceBuilder::new("toolkit", vec![en_us.clone()], "toolkit/{locale}/")
+            .options(options.clone())
+            .reporter(env.clone())
+            .build(fetcher.clone())
+            .unwrap()
+    };
+
+    let fs1 = build();
+    assert!(fs1
+        .fetch_file_sync(&en_us, &"menu.ftl".into(), false)
+        .is_some());
+    let mut cache = StartupCache::new("build-1");
+    fs1.save_startup_cache(&mut cache);
+
+    // The saved text has the BOM removed, but still matches the manifest.
+    let fs2 = build();
+    assert_eq!(fs2.seed_startup_cache(&cache), 1);
+    assert!(env.errors().is_empty());
+}